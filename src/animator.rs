@@ -0,0 +1,107 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shows the moves made on a level one at a time.
+//!
+//! The game applies moves to its level at once, so that input is never held
+//! back by animations. The animator follows that level with a copy that is
+//! shown on the screen and catches up with it one move at a time.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use game::{Level, Move};
+use painter::Animation;
+
+/// Follows a level, replaying its moves on the copy that is shown.
+pub struct Animator {
+    /// The level as shown on the screen
+    shown: Level,
+    /// The number of moves of the followed level already taken into account
+    seen: usize,
+    /// The moves left to show
+    pending: VecDeque<Move>,
+    /// The move being shown, when it started and how long it lasts
+    current: Option<(Move, Instant, Duration)>,
+}
+
+impl Animator {
+    /// Creates a new instance showing the given level.
+    pub fn new(level: &Level) -> Animator {
+        Animator {
+            shown: level.clone(),
+            seen: level.history().len(),
+            pending: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// Shows the given level at once, dropping the moves left to show.
+    pub fn reset(&mut self, level: &Level) {
+        *self = Animator::new(level);
+    }
+
+    /// Returns the level as shown on the screen.
+    pub fn shown(&self) -> &Level {
+        &self.shown
+    }
+
+    /// Returns true if some moves are still being shown.
+    pub fn is_running(&self) -> bool {
+        self.current.is_some() || !self.pending.is_empty()
+    }
+
+    /// Takes into account the moves made on the level since the last call.
+    ///
+    /// New moves are queued for animation. Undone moves are not animated: the
+    /// level is then shown at once, as it is when animations are disabled.
+    pub fn follow(&mut self, level: &Level, duration: Duration) {
+        let history = level.history();
+        if duration == Duration::from_secs(0) || history.len() < self.seen {
+            self.reset(level);
+        } else {
+            self.pending.extend(&history[self.seen..]);
+            self.seen = history.len();
+        }
+    }
+
+    /// Advances the animation to the current time, given the duration of a
+    /// move, and returns the move to paint.
+    ///
+    /// Moves are sped up when several are waiting, so that the level shown
+    /// keeps up with fast typing.
+    pub fn tick(&mut self, duration: Duration) -> Option<Animation> {
+        let now = Instant::now();
+        if let Some((_, start, length)) = self.current {
+            if now.duration_since(start) >= length {
+                self.current = None;
+            }
+        }
+        if self.current.is_none() {
+            if let Some(step) = self.pending.pop_front() {
+                self.shown.step(step.direction());
+                let length = duration / (self.pending.len() as u32 + 1);
+                self.current = Some((step, now, length));
+            }
+        }
+
+        let (step, start, length) = self.current?;
+        let elapsed = now.duration_since(start).as_secs_f64();
+        Some(Animation {
+            step,
+            remaining: 1.0 - (elapsed / length.as_secs_f64()).min(1.0),
+        })
+    }
+}
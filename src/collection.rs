@@ -0,0 +1,716 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::Path;
+use std::str::FromStr;
+use xml::common::Position as TextPosition;
+use xml::reader::EventReader;
+use xml::reader::XmlEvent;
+use xml::writer::{EmitterConfig, XmlEvent as XmlWriterEvent};
+
+use error::{LevelError, SokobanError};
+use game::{self, Level};
+
+/// Represents a collection of levels along with its metadata.
+#[derive(Default)]
+pub struct Collection {
+    /// The collection's title
+    pub title: String,
+    /// The collection's description
+    pub description: String,
+    /// The author's email address
+    pub email: String,
+    /// The collection's web site
+    pub url: String,
+    /// The collection's author or copyright holder
+    pub copyright: String,
+    /// The levels
+    pub levels: Vec<Level>,
+    /// The errors of the levels skipped when loading leniently
+    pub skipped: Vec<SokobanError>,
+}
+
+/// Represents how to deal with invalid levels when loading a collection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Fail on the first invalid level
+    Strict,
+    /// Skip invalid levels, collecting their errors
+    Lenient,
+}
+
+impl Collection {
+    /// Returns the index of the level designated by its 1-based number in the
+    /// file or by its title.
+    ///
+    /// Titles are compared without regard to case.
+    pub fn find_level(&self, spec: &str) -> Option<usize> {
+        match spec.parse::<usize>() {
+            Ok(n) => self.levels.iter().position(|level| level.number() == n),
+            Err(_) => self
+                .levels
+                .iter()
+                .position(|level| level.title().to_lowercase() == spec.to_lowercase()),
+        }
+    }
+
+    /// Returns the 1-based number of the next level read from the file,
+    /// counting the skipped ones.
+    fn next_index(&self) -> usize {
+        self.levels.len() + self.skipped.len() + 1
+    }
+
+    /// Returns the parsed level, numbered after the levels read so far, or
+    /// deals with its error according to the mode.
+    fn accept(
+        &mut self,
+        result: Result<Level, LevelError>,
+        mode: LoadMode,
+    ) -> Result<Option<Level>, SokobanError> {
+        match (result, mode) {
+            (Ok(mut level), _) => {
+                level.set_number(self.next_index());
+                Ok(Some(level))
+            }
+            (Err(err), LoadMode::Strict) => Err(err.into()),
+            (Err(err), LoadMode::Lenient) => {
+                self.skipped.push(err.into());
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Builds a level collection from a file in the SLC format.
+pub fn load_slc_file<P: AsRef<Path>>(path: P, mode: LoadMode) -> Result<Collection, SokobanError> {
+    let mut collection = Collection::default();
+
+    let mut parser = {
+        let file = File::open(path.as_ref())?;
+        EventReader::new(BufReader::new(file))
+    };
+
+    let mut element = String::new();
+    let mut level_title = String::new();
+    let mut level_copyright = String::new();
+    let mut level_data = String::new();
+    let mut level_source = Vec::new();
+    loop {
+        let event = parser.next();
+        match event {
+            Ok(XmlEvent::StartElement {
+                ref name,
+                ref attributes,
+                ..
+            }) => {
+                element = name.local_name.clone();
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|&attr| attr.name.local_name == key)
+                        .map(|attr| attr.value.clone())
+                };
+                if name.local_name == "L" {
+                    // The row starts right after the `<L>` tag
+                    let start = parser.position();
+                    level_source.push((start.row as usize + 1, start.column as usize + 4));
+                } else if name.local_name == "LevelCollection" {
+                    collection.copyright = attribute("Copyright").unwrap_or_default();
+                } else if name.local_name == "Level" {
+                    level_title = attribute("Id").unwrap_or_default();
+                    level_copyright = attribute("Copyright").unwrap_or_default();
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                element.clear();
+                if name.local_name == "L" {
+                    level_data.push('\n');
+                } else if name.local_name == "Level" {
+                    let index = collection.next_index();
+                    let data = mem::take(&mut level_data);
+                    let source = mem::take(&mut level_source);
+                    let result = parse_level(&data, index, &level_title, &source);
+                    if let Some(mut level) = collection.accept(result, mode)? {
+                        level.set_title(level_title.clone());
+                        level.set_copyright(level_copyright.clone());
+                        level.set_source(source);
+                        collection.levels.push(level);
+                    }
+                }
+            }
+            Ok(XmlEvent::Characters(ref data)) => match element.as_str() {
+                "Title" => collection.title.push_str(data.trim()),
+                "Description" => collection.description.push_str(data.trim()),
+                "Email" => collection.email.push_str(data.trim()),
+                "Url" => collection.url.push_str(data.trim()),
+                "L" => level_data.push_str(data),
+                _ => {}
+            },
+            Ok(XmlEvent::Whitespace(ref data)) if element == "L" => level_data.push_str(data),
+            Ok(XmlEvent::EndDocument) => break,
+            Err(err) => return Err(err.into()),
+            _ => {}
+        }
+    }
+
+    Ok(collection)
+}
+
+/// Builds a level collection from a plain text file in the XSB format.
+///
+/// Levels are separated by blank lines. A line of text right before a board
+/// is taken as the level's title. `Title:` and `Author:` lines apply to the
+/// collection before the first board and to the preceding level afterwards;
+/// other `Key: value` lines are ignored. The remaining text before the first
+/// board makes up the description.
+pub fn load_xsb_file<P: AsRef<Path>>(path: P, mode: LoadMode) -> Result<Collection, SokobanError> {
+    let mut text = String::new();
+    File::open(path.as_ref())?.read_to_string(&mut text)?;
+    parse_xsb(&text, mode)
+}
+
+/// Builds a level collection from text in the XSB format.
+pub fn parse_xsb(text: &str, mode: LoadMode) -> Result<Collection, SokobanError> {
+    let mut collection = Collection::default();
+    let mut description = Vec::new();
+    let mut board = String::new();
+    let mut source = Vec::new();
+    let mut pending_title: Option<String> = None;
+    let mut level_title: Option<String> = None;
+    // Whether the last board was kept, once a board was read
+    let mut kept = None;
+
+    for (number, line) in text.lines().map(|l| l.trim_end()).enumerate() {
+        if is_board_line(line) {
+            if board.is_empty() {
+                level_title = pending_title.take();
+            }
+            board.push_str(line);
+            board.push('\n');
+            source.push((number + 1, 1));
+            continue;
+        }
+
+        if !board.is_empty() {
+            kept = Some(push_xsb_level(
+                &mut collection,
+                &board,
+                &mut source,
+                level_title.take(),
+                mode,
+            )?);
+            board.clear();
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let (key, value) = match trimmed.find(':') {
+            Some(i) if !trimmed[..i].contains(char::is_whitespace) => {
+                (trimmed[..i].to_lowercase(), trimmed[i + 1..].trim())
+            }
+            _ => (String::new(), trimmed),
+        };
+        // The metadata of a skipped level is dropped along with it
+        let level = match kept {
+            Some(true) => collection.levels.last_mut(),
+            _ => None,
+        };
+        let header = kept.is_none();
+        match (key.as_str(), level) {
+            ("title", Some(level)) => level.set_title(value),
+            ("title", None) if header => collection.title = value.to_string(),
+            ("author", Some(level)) => level.set_copyright(value),
+            ("author", None) if header => collection.copyright = value.to_string(),
+            ("email", None) if header => collection.email = value.to_string(),
+            ("url", None) if header => collection.url = value.to_string(),
+            ("", _) => {
+                let text = trimmed.trim_start_matches(';').trim().to_string();
+                if header {
+                    if let Some(previous) = pending_title.take() {
+                        description.push(previous);
+                    }
+                }
+                pending_title = Some(text);
+            }
+            _ => {}
+        }
+    }
+    if !board.is_empty() {
+        push_xsb_level(
+            &mut collection,
+            &board,
+            &mut source,
+            level_title.take(),
+            mode,
+        )?;
+    }
+
+    collection.description = description.join("\n");
+    Ok(collection)
+}
+
+/// Returns true if the line looks like a row of a board, possibly run-length
+/// encoded.
+///
+/// Lines starting with a wall are always taken as rows, so that invalid
+/// characters are reported rather than the row being mistaken for text.
+fn is_board_line(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+        || (line.contains('#')
+            && line
+                .chars()
+                .all(|c| "#@+$*. -_|".contains(c) || c.is_ascii_digit()))
+}
+
+/// Parses a board and appends it to the collection.
+///
+/// Returns false if the board was skipped.
+///
+/// The source positions of the rows are consumed. They are only kept for boards
+/// that are not run-length encoded, whose rows match the lines of the file.
+fn push_xsb_level(
+    collection: &mut Collection,
+    board: &str,
+    source: &mut Vec<(usize, usize)>,
+    title: Option<String>,
+    mode: LoadMode,
+) -> Result<bool, SokobanError> {
+    let index = collection.next_index();
+    let title = title.unwrap_or_else(|| index.to_string());
+    let mut source = mem::take(source);
+    if board.contains(|c: char| c == '|' || c.is_ascii_digit()) {
+        source.clear();
+    }
+    let result = parse_level(board, index, &title, &source);
+    if let Some(mut level) = collection.accept(result, mode)? {
+        level.set_title(title);
+        level.set_source(source);
+        collection.levels.push(level);
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Parses a level of a collection, locating errors in the file with the
+/// line and column where each row starts.
+fn parse_level(
+    board: &str,
+    index: usize,
+    title: &str,
+    source: &[(usize, usize)],
+) -> Result<Level, LevelError> {
+    Level::from_str(board).map_err(|error| LevelError {
+        index,
+        title: title.to_string(),
+        position: game::source_position(source, &error.position()),
+        error,
+    })
+}
+
+/// Builds a level collection from a file, choosing the format from the
+/// extension of the file or, failing that, from its content.
+pub fn load_file<P: AsRef<Path>>(path: P, mode: LoadMode) -> Result<Collection, SokobanError> {
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("slc") | Some("xml") => load_slc_file(path, mode),
+        Some("xsb") | Some("sok") | Some("txt") => load_xsb_file(path, mode),
+        _ => {
+            let mut text = String::new();
+            File::open(path.as_ref())?.read_to_string(&mut text)?;
+            if text.trim_start().starts_with('<') {
+                load_slc_file(path, mode)
+            } else {
+                parse_xsb(&text, mode)
+            }
+        }
+    }
+}
+
+/// Tells where `save_level` saved a level, so that it can be saved again in place.
+#[derive(Copy, Clone, Debug)]
+pub struct SavedLevel {
+    /// The 1-based number of the level in the file
+    pub number: usize,
+    /// The length of a text file before the level was appended to it
+    offset: Option<u64>,
+}
+
+/// Saves a level into a collection file, in the format given by the
+/// extension of the file.
+///
+/// The level replaces the one saved previously, or is added at the end of
+/// the collection. The file is created if it does not exist.
+///
+/// Text files are only appended to, so that their comments and layout are
+/// kept; saving again truncates the file back to where the level was
+/// appended. SLC files are rewritten as a whole, into a temporary file that
+/// then replaces the original one.
+pub fn save_level<P: AsRef<Path>>(
+    path: P,
+    level: &Level,
+    previous: Option<SavedLevel>,
+) -> Result<SavedLevel, SokobanError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("slc") | Some("xml") => save_slc_level(path, level, previous),
+        Some("rle") => append_text_level(path, level, previous, |level| {
+            let mut board = game::encode_rle(&level.to_string());
+            board.push('\n');
+            board
+        }),
+        _ => append_text_level(path, level, previous, |level| level.to_string()),
+    }
+}
+
+/// Saves a level into an SLC file, replacing the file at once.
+fn save_slc_level(
+    path: &Path,
+    level: &Level,
+    previous: Option<SavedLevel>,
+) -> Result<SavedLevel, SokobanError> {
+    let mut collection = if path.exists() {
+        load_slc_file(path, LoadMode::Strict)?
+    } else {
+        Collection::default()
+    };
+    let index = match previous {
+        Some(saved) if saved.number >= 1 && saved.number <= collection.levels.len() => {
+            collection.levels[saved.number - 1] = level.clone();
+            saved.number - 1
+        }
+        _ => {
+            collection.levels.push(level.clone());
+            collection.levels.len() - 1
+        }
+    };
+
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let temporary = path.with_file_name(name);
+    let result = File::create(&temporary)
+        .map_err(SokobanError::from)
+        .and_then(|file| {
+            let mut sink = BufWriter::new(file);
+            write_slc(&collection, &mut sink)?;
+            let file = sink.into_inner().map_err(|err| err.into_error())?;
+            file.sync_all()?;
+            Ok(())
+        })
+        .and_then(|_| fs::rename(&temporary, path).map_err(SokobanError::from));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result?;
+    Ok(SavedLevel {
+        number: index + 1,
+        offset: None,
+    })
+}
+
+/// Appends a level to a text file, using the given function to write the board.
+fn append_text_level<F>(
+    path: &Path,
+    level: &Level,
+    previous: Option<SavedLevel>,
+    board: F,
+) -> Result<SavedLevel, SokobanError>
+where
+    F: Fn(&Level) -> String,
+{
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let (number, offset) = match previous {
+        Some(SavedLevel {
+            number,
+            offset: Some(offset),
+        }) => {
+            file.set_len(offset)?;
+            (number, offset)
+        }
+        _ => {
+            let mut text = Vec::new();
+            file.read_to_end(&mut text)?;
+            let collection = parse_xsb(&String::from_utf8_lossy(&text), LoadMode::Lenient)?;
+            (collection.next_index(), text.len() as u64)
+        }
+    };
+
+    // Make sure the level starts on a line of its own
+    let mut last = [b'\n'];
+    if offset > 0 {
+        file.seek(SeekFrom::Start(offset - 1))?;
+        file.read_exact(&mut last)?;
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    let mut sink = BufWriter::new(file);
+    if last[0] != b'\n' {
+        writeln!(sink)?;
+    }
+    write_text_level(level, &mut sink, &board)?;
+    sink.flush()?;
+    Ok(SavedLevel {
+        number,
+        offset: Some(offset),
+    })
+}
+
+/// Writes a level collection in the SLC format.
+pub fn write_slc<W: Write>(collection: &Collection, sink: W) -> Result<(), SokobanError> {
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(sink);
+
+    let boards: Vec<String> = collection.levels.iter().map(Level::to_string).collect();
+    let sizes: Vec<(String, String)> = collection
+        .levels
+        .iter()
+        .map(|l| (l.extents().0.to_string(), l.extents().1.to_string()))
+        .collect();
+    let extents = collection.levels.iter().map(Level::extents);
+    let max_width = extents.clone().map(|e| e.0).max().unwrap_or(0).to_string();
+    let max_height = extents.map(|e| e.1).max().unwrap_or(0).to_string();
+
+    writer.write(XmlWriterEvent::start_element("SokobanLevels"))?;
+    for &(name, value) in &[
+        ("Title", &collection.title),
+        ("Description", &collection.description),
+        ("Email", &collection.email),
+        ("Url", &collection.url),
+    ] {
+        writer.write(XmlWriterEvent::start_element(name))?;
+        writer.write(XmlWriterEvent::characters(value))?;
+        writer.write(XmlWriterEvent::end_element())?;
+    }
+
+    writer.write(
+        XmlWriterEvent::start_element("LevelCollection")
+            .attr("Copyright", &collection.copyright)
+            .attr("MaxWidth", &max_width)
+            .attr("MaxHeight", &max_height),
+    )?;
+    for ((level, board), size) in collection.levels.iter().zip(&boards).zip(&sizes) {
+        let mut element = XmlWriterEvent::start_element("Level")
+            .attr("Id", level.title())
+            .attr("Width", &size.0)
+            .attr("Height", &size.1);
+        if !level.copyright().is_empty() {
+            element = element.attr("Copyright", level.copyright());
+        }
+        writer.write(element)?;
+        for row in board.lines() {
+            writer.write(XmlWriterEvent::start_element("L"))?;
+            writer.write(XmlWriterEvent::characters(row))?;
+            writer.write(XmlWriterEvent::end_element())?;
+        }
+        writer.write(XmlWriterEvent::end_element())?;
+    }
+    writer.write(XmlWriterEvent::end_element())?;
+    writer.write(XmlWriterEvent::end_element())?;
+    Ok(())
+}
+
+/// Writes a level collection in the XSB format, in a way that `parse_xsb`
+/// reads back.
+pub fn write_xsb<W: Write>(collection: &Collection, sink: W) -> Result<(), SokobanError> {
+    write_text(collection, sink, |level| level.to_string())
+}
+
+/// Writes a level collection in the XSB format with run-length encoded
+/// boards, one per line.
+pub fn write_rle<W: Write>(collection: &Collection, sink: W) -> Result<(), SokobanError> {
+    write_text(collection, sink, |level| {
+        let mut board = game::encode_rle(&level.to_string());
+        board.push('\n');
+        board
+    })
+}
+
+/// Writes a level collection as text, using the given function to write
+/// the boards.
+fn write_text<W, F>(collection: &Collection, mut sink: W, board: F) -> Result<(), SokobanError>
+where
+    W: Write,
+    F: Fn(&Level) -> String,
+{
+    for &(key, value) in &[
+        ("Title", &collection.title),
+        ("Author", &collection.copyright),
+        ("Email", &collection.email),
+        ("Url", &collection.url),
+    ] {
+        if !value.is_empty() {
+            writeln!(sink, "{}: {}", key, value)?;
+        }
+    }
+    for line in collection.description.lines() {
+        writeln!(sink, "{}", line)?;
+    }
+    for level in &collection.levels {
+        write_text_level(level, &mut sink, &board)?;
+    }
+    Ok(())
+}
+
+/// Writes a level as text, after a blank line, using the given function to
+/// write the board.
+fn write_text_level<W, F>(level: &Level, mut sink: W, board: F) -> Result<(), SokobanError>
+where
+    W: Write,
+    F: Fn(&Level) -> String,
+{
+    writeln!(sink)?;
+    writeln!(sink, "; {}", level.title())?;
+    write!(sink, "{}", board(level))?;
+    if !level.copyright().is_empty() {
+        writeln!(sink, "Author: {}", level.copyright())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const XSB: &str = "Title: Test Collection
+Author: Jane Doe
+A collection for the tests
+
+; First
+#######
+#@ $ .#
+#######
+Author: John Smith
+
+; Second
+#####
+#@$.#
+#####
+";
+
+    #[test]
+    fn round_trips_through_slc() {
+        let collection = parse_xsb(XSB, LoadMode::Strict).unwrap();
+        let path = env::temp_dir().join(format!("sokoban-rs-test-{}.slc", std::process::id()));
+        write_slc(&collection, File::create(&path).unwrap()).unwrap();
+        let loaded = load_slc_file(&path, LoadMode::Strict);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        let mut text = Vec::new();
+        write_xsb(&loaded, &mut text).unwrap();
+        let reloaded = parse_xsb(&String::from_utf8(text).unwrap(), LoadMode::Strict).unwrap();
+
+        for c in &[&loaded, &reloaded] {
+            assert_eq!(c.title, "Test Collection");
+            assert_eq!(c.copyright, "Jane Doe");
+            assert_eq!(c.description, "A collection for the tests");
+            assert_eq!(c.levels.len(), 2);
+            for (level, original) in c.levels.iter().zip(&collection.levels) {
+                assert_eq!(level.title(), original.title());
+                assert_eq!(level.copyright(), original.copyright());
+                assert_eq!(level.to_string(), original.to_string());
+                assert_eq!(level.number(), original.number());
+            }
+        }
+        assert_eq!(collection.levels[0].title(), "First");
+        assert_eq!(collection.levels[0].copyright(), "John Smith");
+        assert_eq!(collection.levels[1].title(), "Second");
+    }
+
+    #[test]
+    fn ignores_slc_level_sizes() {
+        let text = "<SokobanLevels><LevelCollection>\
+            <Level Id=\"Big\" Width=\"50000\" Height=\"50000\">\
+            <L>#####</L><L>#@$.#</L><L>#####</L></Level>\
+            </LevelCollection></SokobanLevels>";
+        let path = env::temp_dir().join(format!("sokoban-rs-size-{}.slc", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let loaded = load_slc_file(&path, LoadMode::Strict);
+        fs::remove_file(&path).unwrap();
+        let level = &loaded.unwrap().levels[0];
+        assert_eq!(level.extents(), (5, 3));
+        assert_eq!(level.to_string(), "#####\n#@$.#\n#####\n");
+    }
+
+    #[test]
+    fn numbers_levels_as_in_the_file() {
+        let text = XSB.replace("#@$.#\n#####\n", "#@$.X\n#####\nAuthor: Lost\n")
+            + "\n; Third\n#####\n#@$.#\n#####\nAuthor: Ann\n";
+        assert!(parse_xsb(&text, LoadMode::Strict).is_err());
+
+        let collection = parse_xsb(&text, LoadMode::Lenient).unwrap();
+        assert_eq!(collection.skipped.len(), 1);
+        let numbers: Vec<_> = collection.levels.iter().map(Level::number).collect();
+        assert_eq!(numbers, vec![1, 3]);
+        assert_eq!(collection.find_level("3"), Some(1));
+        assert_eq!(collection.find_level("2"), None);
+        assert_eq!(collection.find_level("third"), Some(1));
+        assert_eq!(collection.levels[0].copyright(), "John Smith");
+        assert_eq!(collection.levels[1].copyright(), "Ann");
+    }
+
+    #[test]
+    fn saves_levels_again_in_place() {
+        let path = env::temp_dir().join(format!("sokoban-rs-save-{}.xsb", std::process::id()));
+        // A comment and a last line without a newline must be kept
+        let original = "; My levels\n\n#####\n#@$.#\n#####";
+        fs::write(&path, original).unwrap();
+
+        let mut level: Level = "######\n#@$ .#\n######".parse().unwrap();
+        level.set_title("New");
+        let saved = save_level(&path, &level, None).unwrap();
+        assert_eq!(saved.number, 2);
+        let first = fs::read_to_string(&path).unwrap();
+
+        level = "#######\n#@ $ .#\n#######".parse().unwrap();
+        level.set_title("Newer");
+        let saved = save_level(&path, &level, Some(saved)).unwrap();
+        assert_eq!(saved.number, 2);
+        let second = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            first,
+            format!("{}\n\n; New\n######\n#@$ .#\n######\n", original)
+        );
+        assert_eq!(
+            second,
+            format!("{}\n\n; Newer\n#######\n#@ $ .#\n#######\n", original)
+        );
+        let collection = parse_xsb(&second, LoadMode::Strict).unwrap();
+        assert_eq!(collection.levels.len(), 2);
+        assert_eq!(collection.levels[1].title(), "Newer");
+    }
+}
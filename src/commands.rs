@@ -0,0 +1,195 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The command line subcommands, which run without initializing SDL.
+
+use clap::ArgMatches;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use collection::{self, Collection, LoadMode};
+use game::Position;
+use solver::{Budget, Mode, Outcome, Solver};
+
+/// Loads a level collection, skipping invalid levels with a warning unless
+/// the `--strict` flag is present.
+pub fn load_collection(path: &str, matches: &ArgMatches) -> Result<Collection, Box<dyn Error>> {
+    let collection = collection::load_file(path, load_mode(matches))?;
+    for err in &collection.skipped {
+        eprintln!("warning: skipped {}", err);
+    }
+    Ok(collection)
+}
+
+/// Returns the loading mode selected on the command line.
+fn load_mode(matches: &ArgMatches) -> LoadMode {
+    if matches.is_present("strict") {
+        LoadMode::Strict
+    } else {
+        LoadMode::Lenient
+    }
+}
+
+/// Converts a level collection to another format.
+pub fn convert(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output");
+    let collection = load_collection(input, matches)?;
+
+    let extension = output
+        .and_then(|o| Path::new(o).extension())
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let format = match (matches.value_of("format"), extension.as_deref()) {
+        (Some(format), _) => format,
+        (None, Some("slc")) | (None, Some("xml")) => "slc",
+        (None, Some("rle")) => "rle",
+        _ => "xsb",
+    };
+
+    let mut sink: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout()),
+    };
+    match format {
+        "slc" => collection::write_slc(&collection, &mut sink)?,
+        "rle" => collection::write_rle(&collection, &mut sink)?,
+        _ => collection::write_xsb(&collection, &mut sink)?,
+    }
+    sink.flush()?;
+    Ok(())
+}
+
+/// Solves every level of a collection and prints a report.
+///
+/// Fails if some level could not be solved, so that it can be used in batch jobs.
+pub fn solve(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let collection = load_collection(matches.value_of("input").unwrap(), matches)?;
+    let mode = match matches.value_of("mode") {
+        Some("moves") => Mode::Moves,
+        _ => Mode::Pushes,
+    };
+    let budget = Budget {
+        nodes: Some(value_t!(matches.value_of("nodes"), usize)?),
+        time: match matches.value_of("time") {
+            Some(_) => Some(Duration::from_secs(value_t!(
+                matches.value_of("time"),
+                u64
+            )?)),
+            None => None,
+        },
+    };
+    let mut output = match matches.value_of("output") {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+
+    let solver = Solver::new(mode, budget);
+    let mut unsolved = 0;
+    println!(
+        "{:>4}  {:<24} {:<10} {:>7} {:>7} {:>9} {:>10}",
+        "#", "title", "status", "moves", "pushes", "time (s)", "nodes"
+    );
+    for level in &collection.levels {
+        let (outcome, stats) = solver.solve(level);
+        let (status, moves, pushes) = match outcome {
+            Outcome::Solved(ref solution) => (
+                "solved",
+                solution.moves.to_string(),
+                solution.pushes.to_string(),
+            ),
+            Outcome::Unsolvable => ("unsolvable", "-".to_string(), "-".to_string()),
+            Outcome::Exhausted => ("gave up", "-".to_string(), "-".to_string()),
+        };
+        println!(
+            "{:>4}  {:<24} {:<10} {:>7} {:>7} {:>9.3} {:>10}",
+            level.number(),
+            level.title(),
+            status,
+            moves,
+            pushes,
+            stats.elapsed.as_secs_f64(),
+            stats.nodes
+        );
+
+        match outcome {
+            Outcome::Solved(solution) => {
+                if let Some(ref mut out) = output {
+                    writeln!(out, "; {}", level.title())?;
+                    writeln!(out, "{}", solution.lurd)?;
+                    writeln!(out)?;
+                }
+            }
+            _ => unsolved += 1,
+        }
+    }
+    if let Some(ref mut out) = output {
+        out.flush()?;
+    }
+
+    if unsolved > 0 {
+        return Err(format!(
+            "{} of {} levels not solved",
+            unsolved,
+            collection.levels.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks the structure of every level of a collection and reports the
+/// problems with their positions in the file.
+///
+/// Fails if some problem was found.
+pub fn validate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = matches.value_of("input").unwrap();
+    let collection = collection::load_file(input, load_mode(matches))?;
+
+    let mut count = collection.skipped.len();
+    for err in &collection.skipped {
+        println!("{}: {}", input, err);
+    }
+    for level in &collection.levels {
+        for problem in level.validate() {
+            let pos = problem.position().unwrap_or_else(|| Position::new(0, 0));
+            let location = match level.source_position(&pos) {
+                Some((line, column)) => format!("{}:{}:{}", input, line, column),
+                None => input.to_string(),
+            };
+            let row_column = match problem.position() {
+                Some(pos) => format!(", row {}, column {}", pos.row(), pos.column()),
+                None => String::new(),
+            };
+            println!(
+                "{}: level {} ({}){}: {}",
+                location,
+                level.number(),
+                level.title(),
+                row_column,
+                problem
+            );
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        return Err(format!("{} problems found", count).into());
+    }
+    Ok(())
+}
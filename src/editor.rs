@@ -0,0 +1,257 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The board being designed in the level editor.
+
+use std::cmp;
+use std::collections::HashSet;
+use std::fmt;
+
+use game::{Level, Position};
+
+/// Represents what the editor paints onto the board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tool {
+    /// Paints walls
+    Wall,
+    /// Clears walls, targets and boxes
+    Floor,
+    /// Toggles targets
+    Target,
+    /// Paints boxes
+    Box,
+    /// Moves the player
+    Player,
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Tool::Wall => "wall",
+            Tool::Floor => "floor",
+            Tool::Target => "target",
+            Tool::Box => "box",
+            Tool::Player => "player",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Represents a board being edited.
+///
+/// The board always has exactly one player, which can be moved but not erased.
+#[derive(Clone, Debug)]
+pub struct Editor {
+    /// The title of the level
+    title: String,
+    /// The author or copyright holder of the level
+    copyright: String,
+    /// The number of columns and rows
+    extents: (i32, i32),
+    /// The positions of the walls
+    walls: HashSet<Position>,
+    /// The positions of the targets
+    squares: HashSet<Position>,
+    /// The positions of the boxes
+    boxes: HashSet<Position>,
+    /// The position of the player
+    player: Position,
+}
+
+impl Editor {
+    /// Creates an empty board of the given number of columns and rows, surrounded by walls.
+    pub fn new(extents: (i32, i32)) -> Editor {
+        let extents = (cmp::max(extents.0, 1), cmp::max(extents.1, 1));
+        let mut editor = Editor {
+            title: String::new(),
+            copyright: String::new(),
+            extents,
+            walls: HashSet::new(),
+            squares: HashSet::new(),
+            boxes: HashSet::new(),
+            player: Position::new(extents.1 / 2, extents.0 / 2),
+        };
+        for r in 0..extents.1 {
+            for c in 0..extents.0 {
+                if r == 0 || c == 0 || r == extents.1 - 1 || c == extents.0 - 1 {
+                    editor.apply(Tool::Wall, &Position::new(r, c));
+                }
+            }
+        }
+        editor
+    }
+
+    /// Creates a board from the current state of a level.
+    pub fn from_level(level: &Level) -> Editor {
+        let (cols, rows) = level.extents();
+        let mut editor = Editor {
+            title: level.title().to_string(),
+            copyright: level.copyright().to_string(),
+            extents: (cmp::max(cols, 1), cmp::max(rows, 1)),
+            walls: HashSet::new(),
+            squares: HashSet::new(),
+            boxes: HashSet::new(),
+            player: level.player(),
+        };
+        for r in 0..rows {
+            for c in 0..cols {
+                let pos = Position::new(r, c);
+                if level.is_wall(&pos) {
+                    editor.walls.insert(pos);
+                }
+                if level.is_square(&pos) {
+                    editor.squares.insert(pos);
+                }
+                if level.is_box(&pos) {
+                    editor.boxes.insert(pos);
+                }
+            }
+        }
+        editor.clamp_player();
+        editor
+    }
+
+    /// Returns the number of columns and rows of the board.
+    pub fn extents(&self) -> (i32, i32) {
+        self.extents
+    }
+
+    /// Returns the title
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Changes the title
+    pub fn set_title<S: Into<String>>(&mut self, title: S) {
+        self.title = title.into();
+    }
+
+    /// Changes the author or copyright holder
+    pub fn set_copyright<S: Into<String>>(&mut self, copyright: S) {
+        self.copyright = copyright.into();
+    }
+
+    /// Changes the number of columns and rows of the board, keeping its top-left corner.
+    ///
+    /// Whatever falls outside the new extents is removed, and the player is
+    /// moved back inside if needed.
+    pub fn resize(&mut self, extents: (i32, i32)) {
+        self.extents = (cmp::max(extents.0, 1), cmp::max(extents.1, 1));
+        let (cols, rows) = self.extents;
+        let inside = |pos: &Position| pos.column() < cols && pos.row() < rows;
+        self.walls.retain(&inside);
+        self.squares.retain(&inside);
+        self.boxes.retain(&inside);
+        self.clamp_player();
+    }
+
+    /// Paints the given position with a tool.
+    ///
+    /// Returns false if the position is outside the board or if walls and
+    /// boxes would cover the player.
+    pub fn apply(&mut self, tool: Tool, pos: &Position) -> bool {
+        let (cols, rows) = self.extents;
+        if pos.row() < 0 || pos.row() >= rows || pos.column() < 0 || pos.column() >= cols {
+            return false;
+        }
+        match tool {
+            Tool::Wall | Tool::Box if *pos == self.player => return false,
+            Tool::Wall => {
+                self.squares.remove(pos);
+                self.boxes.remove(pos);
+                self.walls.insert(*pos);
+            }
+            Tool::Floor => {
+                self.walls.remove(pos);
+                self.squares.remove(pos);
+                self.boxes.remove(pos);
+            }
+            Tool::Target => {
+                self.walls.remove(pos);
+                if !self.squares.insert(*pos) {
+                    self.squares.remove(pos);
+                }
+            }
+            Tool::Box => {
+                self.walls.remove(pos);
+                self.boxes.insert(*pos);
+            }
+            Tool::Player => {
+                self.walls.remove(pos);
+                self.boxes.remove(pos);
+                self.player = *pos;
+            }
+        }
+        true
+    }
+
+    /// Returns a playable level made of the board.
+    pub fn to_level(&self) -> Level {
+        let mut level: Level = self
+            .to_string()
+            .parse()
+            .expect("The editor only produces valid characters");
+        level.set_title(self.title.clone());
+        level.set_copyright(self.copyright.clone());
+        level.reserve_extents(self.extents);
+        level
+    }
+
+    /// Moves the player back inside the board and off walls and boxes.
+    fn clamp_player(&mut self) {
+        let (cols, rows) = self.extents;
+        self.player = Position::new(
+            cmp::min(cmp::max(self.player.row(), 0), rows - 1),
+            cmp::min(cmp::max(self.player.column(), 0), cols - 1),
+        );
+        self.walls.remove(&self.player);
+        self.boxes.remove(&self.player);
+    }
+}
+
+impl fmt::Display for Editor {
+    /// Writes the board in the XSB format, one row per line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (cols, rows) = self.extents;
+        for r in 0..rows {
+            let mut line = String::new();
+            for c in 0..cols {
+                let pos = Position::new(r, c);
+                let target = self.squares.contains(&pos);
+                line.push(if self.walls.contains(&pos) {
+                    '#'
+                } else if pos == self.player {
+                    if target {
+                        '+'
+                    } else {
+                        '@'
+                    }
+                } else if self.boxes.contains(&pos) {
+                    if target {
+                        '*'
+                    } else {
+                        '$'
+                    }
+                } else if target {
+                    '.'
+                } else {
+                    ' '
+                });
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
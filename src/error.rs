@@ -18,12 +18,40 @@ use std::convert::From;
 use std::error;
 use std::fmt::{self, Display, Formatter};
 use std::io;
+use xml;
 
 /// Represents an application error
 #[derive(Debug)]
 pub enum SokobanError {
     IoError(io::Error),
     ParseError(game::InvalidChar),
+    LevelError(LevelError),
+    ReplayError(game::InvalidMove),
+    XmlReadError(xml::reader::Error),
+    XmlWriteError(xml::writer::Error),
+}
+
+/// Represents an error in a level of a collection file
+#[derive(Debug)]
+pub struct LevelError {
+    /// The 1-based number of the level in the collection
+    pub index: usize,
+    /// The level's title
+    pub title: String,
+    /// The 1-based file line and column of the error, if known
+    pub position: Option<(usize, usize)>,
+    /// The parsing error
+    pub error: game::InvalidChar,
+}
+
+impl Display for LevelError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "level {} ({})", self.index, self.title)?;
+        if let Some((line, column)) = self.position {
+            write!(f, ", line {}, column {}", line, column)?;
+        }
+        write!(f, ": {}", self.error)
+    }
 }
 
 impl error::Error for SokobanError {
@@ -31,6 +59,10 @@ impl error::Error for SokobanError {
         match *self {
             SokobanError::IoError(..) => "I/O error",
             SokobanError::ParseError(..) => "Level parsing error",
+            SokobanError::LevelError(..) => "Level parsing error",
+            SokobanError::ReplayError(..) => "Solution replay error",
+            SokobanError::XmlReadError(..) => "XML reading error",
+            SokobanError::XmlWriteError(..) => "XML writing error",
         }
     }
 }
@@ -40,6 +72,10 @@ impl Display for SokobanError {
         match *self {
             SokobanError::IoError(ref err) => write!(f, "{}", *err),
             SokobanError::ParseError(ref err) => write!(f, "{}", *err),
+            SokobanError::LevelError(ref err) => write!(f, "{}", *err),
+            SokobanError::ReplayError(ref err) => write!(f, "{}", *err),
+            SokobanError::XmlReadError(ref err) => write!(f, "{}", *err),
+            SokobanError::XmlWriteError(ref err) => write!(f, "{}", *err),
         }
     }
 }
@@ -55,3 +91,27 @@ impl From<game::InvalidChar> for SokobanError {
         SokobanError::ParseError(err)
     }
 }
+
+impl From<game::InvalidMove> for SokobanError {
+    fn from(err: game::InvalidMove) -> Self {
+        SokobanError::ReplayError(err)
+    }
+}
+
+impl From<LevelError> for SokobanError {
+    fn from(err: LevelError) -> Self {
+        SokobanError::LevelError(err)
+    }
+}
+
+impl From<xml::reader::Error> for SokobanError {
+    fn from(err: xml::reader::Error) -> Self {
+        SokobanError::XmlReadError(err)
+    }
+}
+
+impl From<xml::writer::Error> for SokobanError {
+    fn from(err: xml::writer::Error) -> Self {
+        SokobanError::XmlWriteError(err)
+    }
+}
@@ -13,12 +13,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
 /// Represents a direction.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Direction {
     /// Up
     Up,
@@ -30,6 +31,72 @@ pub enum Direction {
     Right,
 }
 
+impl Direction {
+    /// Returns the lowercase LURD letter of the direction.
+    pub fn to_char(self) -> char {
+        match self {
+            Direction::Left => 'l',
+            Direction::Up => 'u',
+            Direction::Right => 'r',
+            Direction::Down => 'd',
+        }
+    }
+
+    /// Returns the opposite direction.
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// Represents a move made by the player.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    /// The direction of the move
+    direction: Direction,
+    /// Whether a box was pushed during the move
+    push: bool,
+}
+
+impl Move {
+    /// Returns the direction of the move.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Returns true if a box was pushed during the move.
+    pub fn is_push(&self) -> bool {
+        self.push
+    }
+
+    /// Returns the LURD notation of the move: lowercase for a simple move,
+    /// uppercase for a push.
+    pub fn to_char(&self) -> char {
+        let c = self.direction.to_char();
+        if self.push {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        }
+    }
+}
+
+/// The largest repeat count in run-length encoded boards and solutions, so
+/// that a corrupted count cannot exhaust the memory
+const MAX_REPEAT: u32 = 1000;
+
+/// The four directions, in LURD order.
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+];
+
 /// Represents a position in the world.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Position(i32, i32);
@@ -66,10 +133,20 @@ impl Position {
 pub struct Level {
     /// The level's title
     title: String,
+    /// The level's author or copyright holder
+    copyright: String,
+    /// The 1-based number of the level in its collection file, skipped levels included
+    number: usize,
+    /// The file line and column where each row starts, if loaded from a file
+    source: Vec<(usize, usize)>,
+    /// Every player position found while parsing
+    players: Vec<Position>,
     /// The player's position
     player: Position,
     /// The current number of steps
     steps: i32,
+    /// The current number of pushes
+    pushes: i32,
     /// The positions of the walls
     walls: HashSet<Position>,
     /// The positions of the boxes
@@ -78,28 +155,274 @@ pub struct Level {
     squares: HashSet<Position>,
     /// The number of columns and rows in the level
     extents: (i32, i32),
+    /// The squares from which a box can never reach a target
+    dead_squares: HashSet<Position>,
+    /// The boxes that can no longer all be brought onto targets
+    deadlocked: HashSet<Position>,
+    /// The moves made so far
+    history: Vec<Move>,
+    /// The indices in the history where each action starts
+    actions: Vec<usize>,
+    /// The actions that have been undone and can be redone
+    undone: Vec<Vec<Move>>,
 }
 
 impl Level {
     /// Moves the player in the given direction if possible.
-    pub fn step(&mut self, dir: Direction) {
-        let next_to_player = self.player.neighbor(dir);
-        if self.is_free(&next_to_player) {
-            self.move_player(next_to_player);
-        } else if self.is_box(&next_to_player) {
-            let next_to_box = next_to_player.neighbor(dir);
-            if self.is_free(&next_to_box) {
-                self.move_box(&next_to_player, next_to_box);
-                self.move_player(next_to_player);
+    ///
+    /// Returns true if the player actually moved. A successful move clears
+    /// the redo history.
+    pub fn step(&mut self, dir: Direction) -> bool {
+        self.play(&[dir])
+    }
+
+    /// Moves the player along the given directions as a single action that
+    /// can be undone at once.
+    ///
+    /// Stops at the first move that is not possible. Returns true if the
+    /// player actually moved.
+    pub fn play(&mut self, dirs: &[Direction]) -> bool {
+        let start = self.history.len();
+        for &dir in dirs {
+            match self.apply(dir) {
+                Some(mv) => self.history.push(mv),
+                None => break,
+            }
+        }
+        if self.history.len() > start {
+            self.actions.push(start);
+            self.undone.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Walks the player to the given position along a shortest path, as a
+    /// single action.
+    ///
+    /// Returns false if the position cannot be reached without pushing a box.
+    pub fn walk_to(&mut self, pos: &Position) -> bool {
+        match self.path_to(pos) {
+            Some(path) => self.play(&path),
+            None => false,
+        }
+    }
+
+    /// Pushes the box at the given position to another position as a single
+    /// action, without moving any other box.
+    ///
+    /// Returns false if there is no such route.
+    pub fn push_box_to(&mut self, from: &Position, to: &Position) -> bool {
+        match self.push_path(from, to) {
+            Some(path) => self.play(&path),
+            None => false,
+        }
+    }
+
+    /// Returns a sequence of moves that brings the box at the given position
+    /// to another position with as few pushes as possible, the player walking
+    /// around the box between pushes. No other box is moved.
+    pub fn push_path(&self, from: &Position, to: &Position) -> Option<Vec<Direction>> {
+        if !self.is_box(from) || (from != to && !self.is_free(to)) {
+            return None;
+        }
+
+        // Search the states of the box and the player, moving the box around
+        // in a scratch copy of the level.
+        let mut scratch = self.clone();
+        scratch.boxes.remove(from);
+        let start = (*from, self.player);
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        let mut found = None;
+        while let Some((b, p)) = queue.pop_front() {
+            if b == *to {
+                found = Some((b, p));
+                break;
+            }
+            scratch.boxes.insert(b);
+            scratch.player = p;
+            for &dir in &DIRECTIONS {
+                let behind = b.neighbor(dir.opposite());
+                let ahead = b.neighbor(dir);
+                let next = (ahead, b);
+                if next != start
+                    && !came_from.contains_key(&next)
+                    && scratch.is_inside(&ahead)
+                    && scratch.is_free(&ahead)
+                    && (behind == p || scratch.path_to(&behind).is_some())
+                {
+                    came_from.insert(next, ((b, p), dir));
+                    queue.push_back(next);
+                }
+            }
+            scratch.boxes.remove(&b);
+        }
+
+        // Rebuild the pushes, then the walks between them
+        let mut state = found?;
+        let mut pushes = Vec::new();
+        while state != start {
+            let (previous, dir) = came_from[&state];
+            pushes.push(dir);
+            state = previous;
+        }
+        pushes.reverse();
+
+        let mut sim = self.clone();
+        let mut box_pos = *from;
+        let mut path = Vec::new();
+        for dir in pushes {
+            let walk = sim.path_to(&box_pos.neighbor(dir.opposite()))?;
+            for &d in walk.iter().chain(Some(&dir)) {
+                sim.apply(d)?;
+                path.push(d);
+            }
+            box_pos = box_pos.neighbor(dir);
+        }
+        Some(path)
+    }
+
+    /// Returns the shortest path of the player to the given position without
+    /// pushing any box.
+    pub fn path_to(&self, pos: &Position) -> Option<Vec<Direction>> {
+        if !self.is_free(pos) {
+            return None;
+        }
+        let mut came_from: HashMap<Position, Direction> = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(self.player);
+        while let Some(current) = queue.pop_front() {
+            if current == *pos {
+                let mut path = Vec::new();
+                let mut cell = current;
+                while cell != self.player {
+                    let dir = came_from[&cell];
+                    path.push(dir);
+                    cell = cell.neighbor(dir.opposite());
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &dir in &DIRECTIONS {
+                let next = current.neighbor(dir);
+                if self.is_inside(&next)
+                    && self.is_free(&next)
+                    && next != self.player
+                    && !came_from.contains_key(&next)
+                {
+                    came_from.insert(next, dir);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Cancels the last action.
+    ///
+    /// Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.actions.pop() {
+            Some(start) => {
+                let mut action = Vec::new();
+                while self.history.len() > start {
+                    let mv = self.history.pop().unwrap();
+                    self.revert(mv);
+                    action.push(mv);
+                }
+                action.reverse();
+                self.undone.push(action);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replays the last undone action.
+    ///
+    /// Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.undone.pop() {
+            Some(action) => {
+                let start = self.history.len();
+                for mv in action {
+                    match self.apply(mv.direction) {
+                        Some(mv) => self.history.push(mv),
+                        None => {
+                            self.undone.clear();
+                            break;
+                        }
+                    }
+                }
+                if self.history.len() > start {
+                    self.actions.push(start);
+                    true
+                } else {
+                    false
+                }
             }
+            None => false,
         }
     }
 
+    /// Returns the moves made so far.
+    pub fn history(&self) -> &[Move] {
+        &self.history
+    }
+
+    /// Returns the moves made so far in LURD notation.
+    pub fn to_lurd(&self) -> String {
+        self.history.iter().map(Move::to_char).collect()
+    }
+
+    /// Plays the moves of a LURD string from the current state.
+    ///
+    /// A move is illegal if the player cannot make it, or if the case of its
+    /// letter does not tell whether it pushes a box. A letter may be preceded
+    /// by a repeat count, as in run-length encoded solutions. Whitespace is
+    /// ignored. On error, the moves preceding the illegal one remain applied.
+    pub fn replay(&mut self, lurd: &str) -> Result<(), InvalidMove> {
+        let mut count = 0;
+        for (index, c) in lurd.chars().enumerate() {
+            if let Some(digit) = c.to_digit(10) {
+                count = match add_digit(count, digit) {
+                    Some(count) => count,
+                    None => return Err(InvalidMove(c, index)),
+                };
+                continue;
+            }
+            let dir = match c.to_ascii_lowercase() {
+                'l' => Direction::Left,
+                'u' => Direction::Up,
+                'r' => Direction::Right,
+                'd' => Direction::Down,
+                c if c.is_whitespace() => continue,
+                _ => return Err(InvalidMove(c, index)),
+            };
+            for _ in 0..cmp::max(count, 1) {
+                let push = self.is_box(&self.player.neighbor(dir));
+                if push != c.is_ascii_uppercase() || !self.step(dir) {
+                    return Err(InvalidMove(c, index));
+                }
+            }
+            count = 0;
+        }
+        Ok(())
+    }
+
     /// Returns the current number of steps.
     pub fn get_steps(&self) -> i32 {
         self.steps
     }
 
+    /// Returns the current number of pushes.
+    pub fn get_pushes(&self) -> i32 {
+        self.pushes
+    }
+
     /// Returns true if the level is completed.
     pub fn is_completed(&self) -> bool {
         self.squares.difference(&self.boxes).count() == 0
@@ -120,6 +443,11 @@ impl Level {
         self.player == *pos
     }
 
+    /// Returns the player's position.
+    pub fn player(&self) -> Position {
+        self.player
+    }
+
     /// Returns true if there is a square at the given position.
     pub fn is_square(&self, pos: &Position) -> bool {
         self.squares.contains(pos)
@@ -130,6 +458,38 @@ impl Level {
         self.walls.contains(pos)
     }
 
+    /// Returns true if a box at the given position can never reach a target.
+    pub fn is_dead_square(&self, pos: &Position) -> bool {
+        self.dead_squares.contains(pos)
+    }
+
+    /// Returns the squares from which a box can never reach a target.
+    pub fn dead_squares(&self) -> &HashSet<Position> {
+        &self.dead_squares
+    }
+
+    /// Returns true if a box stands on a dead square.
+    pub fn has_dead_box(&self) -> bool {
+        self.boxes.iter().any(|b| self.dead_squares.contains(b))
+    }
+
+    /// Returns true if the level can no longer be completed because of
+    /// the position of some boxes.
+    pub fn is_deadlocked(&self) -> bool {
+        !self.deadlocked.is_empty()
+    }
+
+    /// Returns true if the box at the given position is part of a deadlock.
+    pub fn is_deadlocked_box(&self, pos: &Position) -> bool {
+        self.deadlocked.contains(pos)
+    }
+
+    /// Returns true if the given position lies within the extents of the level.
+    pub fn is_inside(&self, pos: &Position) -> bool {
+        let (w, h) = self.extents;
+        pos.row() >= 0 && pos.row() < h && pos.column() >= 0 && pos.column() < w
+    }
+
     /// Returns the number of columns and rows of this level.
     pub fn extents(&self) -> (i32, i32) {
         self.extents
@@ -145,6 +505,251 @@ impl Level {
         self.title = title.into();
     }
 
+    /// Returns the author or copyright holder
+    pub fn copyright(&self) -> &str {
+        &self.copyright
+    }
+
+    /// Changes the author or copyright holder
+    pub fn set_copyright<S: Into<String>>(&mut self, copyright: S) {
+        self.copyright = copyright.into();
+    }
+
+    /// Returns the 1-based number of the level in its collection file,
+    /// counting the levels that were skipped when loading it.
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Changes the number of the level in its collection file
+    pub fn set_number(&mut self, number: usize) {
+        self.number = number;
+    }
+
+    /// Enlarges the extents of the level to at least the given number of
+    /// columns and rows.
+    pub fn reserve_extents(&mut self, extents: (i32, i32)) {
+        self.extents.0 = cmp::max(self.extents.0, extents.0);
+        self.extents.1 = cmp::max(self.extents.1, extents.1);
+    }
+
+    /// Records the 1-based file line and column where each row of the level starts.
+    pub fn set_source(&mut self, source: Vec<(usize, usize)>) {
+        self.source = source;
+    }
+
+    /// Returns the 1-based file line and column of the given position, if known.
+    pub fn source_position(&self, pos: &Position) -> Option<(usize, usize)> {
+        source_position(&self.source, pos)
+    }
+
+    /// Checks the structure of the level and returns the problems found.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        if self.boxes.len() != self.squares.len() {
+            problems.push(Problem::CountMismatch {
+                boxes: self.boxes.len(),
+                squares: self.squares.len(),
+            });
+        }
+
+        match self.players.split_first() {
+            None => problems.push(Problem::MissingPlayer),
+            Some((_, others)) => {
+                problems.extend(others.iter().map(|&pos| Problem::DuplicatePlayer(pos)));
+
+                let interior = self.interior();
+                let mut open: Vec<Position> = interior
+                    .iter()
+                    .filter(|pos| {
+                        DIRECTIONS
+                            .iter()
+                            .any(|&d| !self.is_inside(&pos.neighbor(d)))
+                    })
+                    .cloned()
+                    .collect();
+                open.sort();
+                problems.extend(open.into_iter().map(Problem::OpenBoundary));
+
+                let mut unreachable: Vec<Position> = self
+                    .boxes
+                    .iter()
+                    .filter(|pos| !interior.contains(pos))
+                    .cloned()
+                    .collect();
+                unreachable.sort();
+                problems.extend(unreachable.into_iter().map(Problem::UnreachableBox));
+            }
+        }
+
+        problems
+    }
+
+    /// Returns the positions the player could walk to if there were no boxes.
+    fn interior(&self) -> HashSet<Position> {
+        let mut interior = HashSet::new();
+        let mut queue = VecDeque::new();
+        interior.insert(self.player);
+        queue.push_back(self.player);
+        while let Some(pos) = queue.pop_front() {
+            for &dir in &DIRECTIONS {
+                let next = pos.neighbor(dir);
+                if self.is_inside(&next) && !self.walls.contains(&next) && interior.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        interior
+    }
+
+    /// Computes the interior squares from which a box can never be pushed
+    /// onto a target.
+    ///
+    /// A square is alive if a box can be pulled to it from a target, the
+    /// player standing on the floor behind the box.
+    fn find_dead_squares(&self) -> HashSet<Position> {
+        let interior = self.interior();
+        let mut alive = HashSet::new();
+        let mut queue = VecDeque::new();
+        for pos in self.squares.iter().filter(|pos| interior.contains(pos)) {
+            alive.insert(*pos);
+            queue.push_back(*pos);
+        }
+        while let Some(pos) = queue.pop_front() {
+            for &dir in &DIRECTIONS {
+                let next = pos.neighbor(dir);
+                let behind = next.neighbor(dir);
+                if interior.contains(&next) && interior.contains(&behind) && alive.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        interior.difference(&alive).cloned().collect()
+    }
+
+    /// Recomputes the set of boxes that are part of a deadlock.
+    ///
+    /// A box is deadlocked if it stands on a dead square, if it belongs to a
+    /// group of frozen boxes that are not all on targets, or if it belongs to
+    /// a 2x2 block of boxes and walls that are not all on targets.
+    fn update_deadlocks(&mut self) {
+        let mut deadlocked: HashSet<Position> = self
+            .boxes
+            .iter()
+            .filter(|b| self.dead_squares.contains(b))
+            .cloned()
+            .collect();
+
+        for b in &self.boxes {
+            if deadlocked.contains(b) {
+                continue;
+            }
+            let mut frozen = HashSet::new();
+            if self.is_frozen(*b, &mut frozen) && frozen.iter().any(|f| !self.is_square(f)) {
+                deadlocked.extend(frozen);
+            }
+        }
+
+        for b in &self.boxes {
+            for &(v, h) in &[
+                (Direction::Up, Direction::Left),
+                (Direction::Up, Direction::Right),
+                (Direction::Down, Direction::Left),
+                (Direction::Down, Direction::Right),
+            ] {
+                let block = [*b, b.neighbor(v), b.neighbor(h), b.neighbor(v).neighbor(h)];
+                let is_blocked = block.iter().all(|p| self.is_wall(p) || self.is_box(p));
+                let is_on_target = block.iter().all(|p| !self.is_box(p) || self.is_square(p));
+                if is_blocked && !is_on_target {
+                    deadlocked.extend(block.iter().filter(|p| self.is_box(p)));
+                }
+            }
+        }
+
+        self.deadlocked = deadlocked;
+    }
+
+    /// Returns true if the box at the given position can be moved neither
+    /// horizontally nor vertically.
+    ///
+    /// The boxes examined along the way are collected in `frozen` and treated
+    /// as walls; they are removed again if this box turns out not to be frozen.
+    fn is_frozen(&self, pos: Position, frozen: &mut HashSet<Position>) -> bool {
+        let before = frozen.clone();
+        frozen.insert(pos);
+        let result = self.is_blocked(pos, Direction::Left, Direction::Right, frozen)
+            && self.is_blocked(pos, Direction::Up, Direction::Down, frozen);
+        if !result {
+            *frozen = before;
+        }
+        result
+    }
+
+    /// Returns true if the box at the given position cannot be pushed along
+    /// the axis of the given directions.
+    fn is_blocked(
+        &self,
+        pos: Position,
+        a: Direction,
+        b: Direction,
+        frozen: &mut HashSet<Position>,
+    ) -> bool {
+        let (first, second) = (pos.neighbor(a), pos.neighbor(b));
+        if self.is_wall(&first) || self.is_wall(&second) {
+            return true;
+        }
+        if frozen.contains(&first) || frozen.contains(&second) {
+            return true;
+        }
+        if self.is_dead_square(&first) && self.is_dead_square(&second) {
+            return true;
+        }
+        (self.is_box(&first) && self.is_frozen(first, frozen))
+            || (self.is_box(&second) && self.is_frozen(second, frozen))
+    }
+
+    /// Moves the player in the given direction, pushing a box if needed,
+    /// without recording anything in the history.
+    fn apply(&mut self, dir: Direction) -> Option<Move> {
+        let next_to_player = self.player.neighbor(dir);
+        if self.is_free(&next_to_player) {
+            self.move_player(next_to_player);
+            Some(Move {
+                direction: dir,
+                push: false,
+            })
+        } else if self.is_box(&next_to_player) {
+            let next_to_box = next_to_player.neighbor(dir);
+            if self.is_free(&next_to_box) {
+                self.move_box(&next_to_player, next_to_box);
+                self.move_player(next_to_player);
+                self.update_deadlocks();
+                Some(Move {
+                    direction: dir,
+                    push: true,
+                })
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Cancels a move without recording anything in the history.
+    fn revert(&mut self, mv: Move) {
+        let dir = mv.direction;
+        let previous = self.player.neighbor(dir.opposite());
+        if mv.push && self.boxes.remove(&self.player.neighbor(dir)) {
+            self.boxes.insert(self.player);
+            self.pushes -= 1;
+            self.update_deadlocks();
+        }
+        self.player = previous;
+        self.steps -= 1;
+    }
+
     /// moves the player to the given position.
     fn move_player(&mut self, pos: Position) {
         if pos != self.player {
@@ -157,6 +762,7 @@ impl Level {
     fn move_box(&mut self, from: &Position, to: Position) {
         if self.boxes.remove(from) {
             self.boxes.insert(to);
+            self.pushes += 1;
         }
     }
 }
@@ -165,6 +771,18 @@ impl Level {
 #[derive(Debug)]
 pub struct InvalidChar(char, Position);
 
+impl InvalidChar {
+    /// Returns the invalid character.
+    pub fn character(&self) -> char {
+        self.0
+    }
+
+    /// Returns the position of the invalid character in the level.
+    pub fn position(&self) -> Position {
+        self.1
+    }
+}
+
 impl Display for InvalidChar {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let InvalidChar(c, pos) = *self;
@@ -178,18 +796,120 @@ impl Display for InvalidChar {
     }
 }
 
+/// Represents an error due to an illegal move in a LURD string.
+#[derive(Debug)]
+pub struct InvalidMove(char, usize);
+
+impl InvalidMove {
+    /// Returns the index of the illegal move in the LURD string.
+    pub fn index(&self) -> usize {
+        self.1
+    }
+}
+
+impl Display for InvalidMove {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let InvalidMove(c, index) = *self;
+        write!(f, "illegal move `{}' at index {}", c, index)
+    }
+}
+
+/// Represents a structural problem of a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The number of boxes differs from the number of targets
+    CountMismatch { boxes: usize, squares: usize },
+    /// There is no player
+    MissingPlayer,
+    /// There is more than one player; the position is that of an extra one
+    DuplicatePlayer(Position),
+    /// The player can reach the edge of the level at the given position
+    OpenBoundary(Position),
+    /// The player can never reach the box at the given position
+    UnreachableBox(Position),
+}
+
+impl Problem {
+    /// Returns the position the problem relates to, if any.
+    pub fn position(&self) -> Option<Position> {
+        match *self {
+            Problem::CountMismatch { .. } | Problem::MissingPlayer => None,
+            Problem::DuplicatePlayer(pos)
+            | Problem::OpenBoundary(pos)
+            | Problem::UnreachableBox(pos) => Some(pos),
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Problem::CountMismatch { boxes, squares } => {
+                write!(f, "{} boxes for {} targets", boxes, squares)
+            }
+            Problem::MissingPlayer => write!(f, "no player"),
+            Problem::DuplicatePlayer(..) => write!(f, "duplicate player"),
+            Problem::OpenBoundary(..) => write!(f, "the player can walk off the level"),
+            Problem::UnreachableBox(..) => write!(f, "box out of the player's reach"),
+        }
+    }
+}
+
+impl Display for Level {
+    /// Writes the current state of the level as an XSB board, one row per
+    /// line without trailing spaces.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (cols, rows) = self.extents;
+        for r in 0..rows {
+            let mut line = String::with_capacity(cols as usize);
+            for c in 0..cols {
+                let pos = Position(r, c);
+                line.push(
+                    match (
+                        self.is_wall(&pos),
+                        self.is_box(&pos),
+                        self.is_player(&pos),
+                        self.is_square(&pos),
+                    ) {
+                        (true, _, _, _) => '#',
+                        (_, true, _, true) => '*',
+                        (_, true, _, false) => '$',
+                        (_, _, true, true) => '+',
+                        (_, _, true, false) => '@',
+                        (_, _, _, true) => '.',
+                        _ => ' ',
+                    },
+                );
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
 impl FromStr for Level {
     type Err = InvalidChar;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = &expand_rle(s)?;
         let mut level = Level {
             title: String::new(),
+            copyright: String::new(),
+            number: 1,
+            source: Vec::new(),
+            players: Vec::new(),
             player: Position(0, 0),
             steps: 0,
+            pushes: 0,
             walls: HashSet::new(),
             boxes: HashSet::new(),
             squares: HashSet::new(),
             extents: (0, 0),
+            dead_squares: HashSet::new(),
+            deadlocked: HashSet::new(),
+            history: Vec::new(),
+            actions: Vec::new(),
+            undone: Vec::new(),
         };
 
         let (mut row, mut col) = (0, 0);
@@ -211,16 +931,18 @@ impl FromStr for Level {
                 }
                 '@' => {
                     level.player = pos;
+                    level.players.push(pos);
                 }
                 '+' => {
                     level.player = pos;
+                    level.players.push(pos);
                     level.squares.insert(pos);
                 }
                 '*' => {
                     level.boxes.insert(pos);
                     level.squares.insert(pos);
                 }
-                ' ' => {}
+                ' ' | '-' | '_' => {}
                 _ => {
                     return Err(InvalidChar(c, pos));
                 }
@@ -244,7 +966,316 @@ impl FromStr for Level {
             }
         }
         level.extents = (w + 1, h + 1);
+        level.dead_squares = level.find_dead_squares();
+        level.update_deadlocks();
 
         Ok(level)
     }
 }
+
+/// Returns the 1-based file line and column of a position, given the file
+/// line and column where each row of the level starts.
+pub fn source_position(source: &[(usize, usize)], pos: &Position) -> Option<(usize, usize)> {
+    if pos.row() < 0 || pos.column() < 0 {
+        return None;
+    }
+    source
+        .get(pos.row() as usize)
+        .map(|&(line, column)| (line, column + pos.column() as usize))
+}
+
+/// Encodes an XSB board with run-length encoding, using `-` for the floor
+/// and `|` to separate the rows.
+pub fn encode_rle(board: &str) -> String {
+    let mut encoded = String::new();
+    let mut chars = board
+        .trim_end_matches('\n')
+        .chars()
+        .map(|c| match c {
+            ' ' => '-',
+            '\n' => '|',
+            c => c,
+        })
+        .peekable();
+    while let Some(c) = chars.next() {
+        let mut count = 1;
+        while chars.peek() == Some(&c) {
+            chars.next();
+            count += 1;
+        }
+        if count > 1 {
+            encoded.push_str(&count.to_string());
+        }
+        encoded.push(c);
+    }
+    encoded
+}
+
+/// Expands a run-length encoded board, where a character may be preceded by
+/// a repeat count and rows may be separated by `|`.
+///
+/// Boards without any digit or `|` are returned unchanged. A repeat count
+/// above `MAX_REPEAT` is reported as an invalid character.
+fn expand_rle(s: &str) -> Result<String, InvalidChar> {
+    let mut expanded = String::with_capacity(s.len());
+    let mut count = 0;
+    let (mut row, mut col) = (0, 0);
+    for c in s.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            count = add_digit(count, digit).ok_or(InvalidChar(c, Position(row, col)))?;
+            continue;
+        }
+        let c = if c == '|' { '\n' } else { c };
+        for _ in 0..cmp::max(count, 1) {
+            expanded.push(c);
+        }
+        if c == '\n' {
+            row += 1;
+            col = 0;
+        } else {
+            col += cmp::max(count, 1) as i32;
+        }
+        count = 0;
+    }
+    Ok(expanded)
+}
+
+/// Appends a decimal digit to a repeat count, unless the count would exceed
+/// `MAX_REPEAT`.
+fn add_digit(count: u32, digit: u32) -> Option<u32> {
+    count
+        .checked_mul(10)
+        .and_then(|count| count.checked_add(digit))
+        .filter(|&count| count <= MAX_REPEAT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORRIDOR: &str = "#######\n#@ $ .#\n#######";
+
+    /// Returns what can be observed of the state of a level.
+    fn snapshot(level: &Level) -> (String, i32, i32, Position, String, bool) {
+        (
+            level.to_string(),
+            level.get_steps(),
+            level.get_pushes(),
+            level.player(),
+            level.to_lurd(),
+            level.has_dead_box(),
+        )
+    }
+
+    #[test]
+    fn undo_and_redo_restore_exact_states() {
+        let mut level: Level = "######\n#@   #\n# $ .#\n#    #\n######".parse().unwrap();
+        let mut states = vec![snapshot(&level)];
+        for &dir in &[Direction::Right, Direction::Down, Direction::Left] {
+            assert!(level.step(dir));
+            states.push(snapshot(&level));
+        }
+        // The box is pushed into the corner and cannot be saved anymore
+        assert!(level.step(Direction::Down));
+        assert!(level.has_dead_box());
+        states.push(snapshot(&level));
+
+        for state in states.iter().rev().skip(1) {
+            assert!(level.undo());
+            assert_eq!(snapshot(&level), *state);
+        }
+        assert!(!level.undo());
+
+        for state in states.iter().skip(1) {
+            assert!(level.redo());
+            assert_eq!(snapshot(&level), *state);
+        }
+        assert!(!level.redo());
+    }
+
+    #[test]
+    fn undo_reverts_a_walk_at_once() {
+        let mut level: Level = CORRIDOR.parse().unwrap();
+        let initial = snapshot(&level);
+        assert!(level.walk_to(&Position::new(1, 2)));
+        assert!(level.step(Direction::Right));
+        assert_eq!(level.to_lurd(), "rR");
+
+        assert!(level.undo());
+        assert_eq!(level.to_lurd(), "r");
+        assert!(level.undo());
+        assert_eq!(snapshot(&level), initial);
+    }
+
+    #[test]
+    fn new_move_clears_redo() {
+        let mut level: Level = CORRIDOR.parse().unwrap();
+        assert!(level.step(Direction::Right));
+        assert!(level.undo());
+        assert!(level.step(Direction::Right));
+        assert!(!level.redo());
+    }
+
+    #[test]
+    fn exports_lurd_with_pushes_in_upper_case() {
+        let mut level: Level = CORRIDOR.parse().unwrap();
+        assert!(level.play(&[Direction::Right, Direction::Right, Direction::Right]));
+        assert!(!level.step(Direction::Right));
+        assert_eq!(level.to_lurd(), "rRR");
+        assert!(level.is_completed());
+    }
+
+    #[test]
+    fn replays_lurd() {
+        let mut level: Level = CORRIDOR.parse().unwrap();
+        level.replay("rRR").unwrap();
+        assert!(level.is_completed());
+        assert_eq!((level.get_steps(), level.get_pushes()), (3, 2));
+
+        let mut level: Level = CORRIDOR.parse().unwrap();
+        level.replay("r 2R\n").unwrap();
+        assert_eq!(level.to_lurd(), "rRR");
+    }
+
+    #[test]
+    fn replay_reports_index_of_illegal_move() {
+        let replay = |lurd: &str| {
+            let mut level: Level = CORRIDOR.parse().unwrap();
+            let index = level.replay(lurd).unwrap_err().index();
+            (index, level.to_lurd())
+        };
+        // A push written in lower case
+        assert_eq!(replay("rrr"), (1, "r".to_string()));
+        // A simple move written in upper case
+        assert_eq!(replay("R"), (0, String::new()));
+        // A move into the wall
+        assert_eq!(replay("rRRR"), (3, "rRR".to_string()));
+        assert_eq!(replay("u"), (0, String::new()));
+        // A character that is not a move
+        assert_eq!(replay("rRx"), (2, "rR".to_string()));
+        // A repeat count running into the wall
+        assert_eq!(replay("r3R"), (2, "rRR".to_string()));
+        // A repeat count too large to be played
+        assert_eq!(replay("99999999999r"), (3, String::new()));
+    }
+
+    #[test]
+    fn expands_rle() {
+        assert_eq!(expand_rle("3#|#@$.#|5#").unwrap(), "###\n#@$.#\n#####");
+        assert_eq!(expand_rle("#-#").unwrap(), "#-#");
+        assert_eq!(expand_rle(CORRIDOR).unwrap(), CORRIDOR);
+
+        let level: Level = "7#|#@-$-.#|7#".parse().unwrap();
+        assert_eq!(level.to_string(), format!("{}\n", CORRIDOR));
+    }
+
+    #[test]
+    fn rejects_huge_rle_counts() {
+        let err = expand_rle("#|99999999999#").unwrap_err();
+        assert_eq!(err.character(), '9');
+        assert_eq!(err.position(), Position::new(1, 0));
+        assert!(expand_rle("1001#").is_err());
+        assert!("4000000000#".parse::<Level>().is_err());
+    }
+
+    #[test]
+    fn encodes_rle() {
+        let board = format!("{}\n", CORRIDOR);
+        assert_eq!(encode_rle(&board), "7#|#@-$-.#|7#");
+        assert_eq!(
+            expand_rle(&encode_rle(&board)).unwrap().replace('-', " "),
+            CORRIDOR
+        );
+    }
+
+    #[test]
+    fn finds_dead_squares() {
+        let level: Level = "#######\n#@   .#\n#  $  #\n#     #\n#######"
+            .parse()
+            .unwrap();
+        // Corners, and walls without a target along them
+        for &(r, c) in &[(1, 1), (2, 1), (3, 1), (3, 3), (3, 5)] {
+            assert!(level.is_dead_square(&Position::new(r, c)));
+        }
+        for &(r, c) in &[(1, 2), (1, 5), (2, 3), (2, 5)] {
+            assert!(!level.is_dead_square(&Position::new(r, c)));
+        }
+        // Walls and the outside of the level are not dead squares
+        assert!(!level.is_dead_square(&Position::new(0, 0)));
+        assert!(!level.has_dead_box());
+    }
+
+    #[test]
+    fn detects_frozen_boxes() {
+        // Two boxes side by side against a wall, off their targets
+        let mut level: Level = "######\n# $$.#\n#   .#\n#@   #\n######".parse().unwrap();
+        assert!(!level.has_dead_box());
+        assert!(level.is_deadlocked());
+        assert!(level.is_deadlocked_box(&Position::new(1, 2)));
+        assert!(level.is_deadlocked_box(&Position::new(1, 3)));
+
+        // The same boxes once on their targets
+        level = "######\n# **.#\n#  $ #\n#@   #\n######".parse().unwrap();
+        assert!(!level.is_deadlocked());
+    }
+
+    #[test]
+    fn detects_blocks_of_boxes() {
+        let mut level: Level = "#######\n#. @ .#\n#  $  #\n# $   #\n# $$  #\n#.   .#\n#######"
+            .parse()
+            .unwrap();
+        assert!(!level.is_deadlocked());
+
+        // Closing a 2x2 block deadlocks its four boxes, though none is on a dead square
+        assert!(level.step(Direction::Down));
+        assert!(!level.has_dead_box());
+        assert!(level.is_deadlocked());
+        for &(r, c) in &[(3, 2), (3, 3), (4, 2), (4, 3)] {
+            assert!(level.is_deadlocked_box(&Position::new(r, c)));
+        }
+
+        assert!(level.undo());
+        assert!(!level.is_deadlocked());
+    }
+
+    #[test]
+    fn routes_pushes_around_the_box() {
+        let board = "#######\n#     #\n# $ # #\n#@   .#\n#######";
+        let mut level: Level = board.parse().unwrap();
+        let (from, to) = (Position::new(2, 2), Position::new(3, 5));
+        assert_eq!(level.push_path(&from, &to).map(|path| path.len()), Some(9));
+
+        assert!(level.push_box_to(&from, &to));
+        assert!(level.is_completed());
+        assert_eq!((level.get_steps(), level.get_pushes()), (9, 4));
+
+        // The whole route is undone at once
+        assert!(level.undo());
+        assert_eq!(level.to_string(), format!("{}\n", board));
+    }
+
+    #[test]
+    fn pushes_no_other_box() {
+        let mut level: Level = "#######\n#@$ $.#\n#######".parse().unwrap();
+        assert_eq!(
+            level.push_path(&Position::new(1, 2), &Position::new(1, 5)),
+            None
+        );
+        assert!(!level.push_box_to(&Position::new(1, 2), &Position::new(1, 5)));
+        assert_eq!(level.get_steps(), 0);
+
+        // Neither into a wall nor from a square without a box
+        assert_eq!(
+            level.push_path(&Position::new(1, 4), &Position::new(0, 4)),
+            None
+        );
+        assert_eq!(
+            level.push_path(&Position::new(1, 3), &Position::new(1, 3)),
+            None
+        );
+        assert_eq!(
+            level.push_path(&Position::new(1, 2), &Position::new(1, 3)),
+            Some(vec![Direction::Right])
+        );
+    }
+}
@@ -14,143 +14,274 @@
 // limitations under the License.
 
 //! This is an implementation of Sokoban in Rust.
+//!
+//! The game is played in an SDL window, or in the terminal when there is no
+//! display. Building without the default `sdl2` feature leaves only the
+//! terminal, along with the command line tools that do not draw.
 
-#[macro_use]
+#[cfg_attr(feature = "sdl2", macro_use)]
 extern crate bitflags;
 #[macro_use]
 extern crate clap;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "sdl2")]
 extern crate sdl2;
 extern crate xml;
 
 use clap::App;
+#[cfg(feature = "sdl2")]
+use clap::ArgMatches;
+#[cfg(feature = "sdl2")]
 use sdl2::event::Event;
-use sdl2::image::LoadTexture;
+#[cfg(feature = "sdl2")]
 use sdl2::image::InitFlag;
-use sdl2::keyboard::Keycode;
-use sdl2::render::{Canvas, TextureCreator};
-use sdl2::video::{Window, WindowContext};
-use sdl2::Sdl;
+#[cfg(feature = "sdl2")]
+use sdl2::image::{LoadTexture, SaveSurface};
+#[cfg(feature = "sdl2")]
+use sdl2::keyboard::{Keycode, Mod};
+#[cfg(feature = "sdl2")]
+use sdl2::mouse::MouseButton;
+#[cfg(feature = "sdl2")]
+use sdl2::pixels::PixelFormatEnum;
+#[cfg(feature = "sdl2")]
+use sdl2::render::{Canvas, RenderTarget, TextureCreator};
+#[cfg(feature = "sdl2")]
+use sdl2::surface::Surface;
+#[cfg(feature = "sdl2")]
+use sdl2::ttf::Sdl2TtfContext;
+#[cfg(feature = "sdl2")]
+use sdl2::video::Window;
+#[cfg(feature = "sdl2")]
+use sdl2::{EventPump, VideoSubsystem};
+#[cfg(feature = "sdl2")]
+use std::cmp;
 use std::error::Error;
-use std::fs::File;
-use std::io::BufReader;
 use std::path::Path;
-use std::str::FromStr;
-use xml::reader::EventReader;
-use xml::reader::XmlEvent;
+use std::process;
+#[cfg(feature = "sdl2")]
+use std::time::Duration;
 
+#[cfg(feature = "sdl2")]
+pub mod animator;
+pub mod collection;
+pub mod commands;
+pub mod editor;
 pub mod error;
 pub mod game;
+#[cfg(feature = "sdl2")]
 pub mod painter;
+pub mod progress;
+#[cfg(feature = "sdl2")]
 pub mod shadow;
+pub mod solver;
+#[cfg(feature = "sdl2")]
 pub mod tileset;
+pub mod tty;
 
-use game::{Direction, Level};
-use painter::Painter;
+#[cfg(feature = "sdl2")]
+use animator::Animator;
+use collection::Collection;
+use editor::Editor;
+#[cfg(feature = "sdl2")]
+use editor::Tool;
+use game::Level;
+#[cfg(feature = "sdl2")]
+use game::{Direction, Position};
+#[cfg(feature = "sdl2")]
+use painter::{LevelSummary, Painter};
+use progress::Progress;
+#[cfg(feature = "sdl2")]
 use tileset::Tileset;
 
-pub fn main() -> Result<(), Box<dyn Error>> {
+/// The time between two frames while moves are being animated, in milliseconds
+#[cfg(feature = "sdl2")]
+const FRAME_TIME: u32 = 16;
+/// The change of the duration of move animations with the + and - keys, in milliseconds
+#[cfg(feature = "sdl2")]
+const MOVE_DURATION_STEP: u64 = 20;
+/// The longest duration of move animations, in milliseconds
+#[cfg(feature = "sdl2")]
+const MAX_MOVE_DURATION: u64 = 1000;
+
+pub fn main() {
+    if let Err(err) = run() {
+        eprintln!("sokoban-rs: {}", err);
+        process::exit(1);
+    }
+}
+
+/// Runs the game or one of the subcommands
+fn run() -> Result<(), Box<dyn Error>> {
     // Read command line arguments
     let yml = load_yaml!("clap.yml");
     let matches = App::from_yaml(yml).get_matches();
-    let width = value_t!(matches.value_of("width"), u32).unwrap_or(1024);
-    let height = value_t!(matches.value_of("height"), u32).unwrap_or(768);
-    let fullscreen = matches.is_present("fullscreen");
-    let slc_file = matches.value_of("slc_file").unwrap();
-
-    // Load the level collection file
-    let levels = load_slc_file(slc_file)?;
-
-    // Initialize SDL components
-    let sdl = sdl2::init()?;
-    let _ = sdl2::image::init(InitFlag::PNG)?;
-    let ttf_context = sdl2::ttf::init()?;
+    match matches.subcommand() {
+        ("convert", Some(m)) => return commands::convert(m),
+        ("solve", Some(m)) => return commands::solve(m),
+        ("validate", Some(m)) => return commands::validate(m),
+        #[cfg(feature = "sdl2")]
+        ("render", Some(m)) => return render(m),
+        #[cfg(not(feature = "sdl2"))]
+        ("render", Some(_)) => return Err("rendering images needs SDL support".into()),
+        _ => {}
+    }
 
-    let window = create_window(&sdl, width, height, fullscreen)?;
-    let mut canvas = window.into_canvas().build()?;
-    let texture_creator = canvas.texture_creator();
+    // Load the level collection file and the player's progress, or the level to edit
+    let mut collection = Collection::default();
+    let mut progress = Progress::load().unwrap_or_else(|err| {
+        eprintln!(
+            "sokoban-rs: cannot load progress, it will not be saved: {}",
+            err
+        );
+        Progress::default()
+    });
+    let mut start = 0;
+    let mut editor = None;
+    let slc_file = match matches.subcommand() {
+        ("edit", Some(m)) => {
+            let file = m.value_of("file").unwrap();
+            let mut e = match m.value_of("level") {
+                Some(spec) => {
+                    let collection = commands::load_collection(file, m)?;
+                    let index = collection
+                        .find_level(spec)
+                        .ok_or_else(|| format!("no level {} in {}", spec, file))?;
+                    Editor::from_level(&collection.levels[index])
+                }
+                None => Editor::new((
+                    value_t!(m.value_of("columns"), i32)?,
+                    value_t!(m.value_of("rows"), i32)?,
+                )),
+            };
+            if let Some(title) = m.value_of("title") {
+                e.set_title(title);
+            }
+            if let Some(author) = m.value_of("author") {
+                e.set_copyright(author);
+            }
+            editor = Some(e);
+            file
+        }
+        _ => {
+            let file = matches.value_of("slc_file").unwrap();
+            collection = commands::load_collection(file, &matches)?;
+            file
+        }
+    };
+    let name = if collection.title.is_empty() {
+        Path::new(slc_file)
+            .file_stem()
+            .map_or(slc_file.into(), |stem| stem.to_string_lossy())
+            .into_owned()
+    } else {
+        collection.title.clone()
+    };
+    if editor.is_none() {
+        start = match matches.value_of("level") {
+            Some(spec) => collection
+                .find_level(spec)
+                .ok_or_else(|| format!("no level {} in {}", spec, slc_file))?,
+            None => collection
+                .levels
+                .iter()
+                .position(|level| progress.record(&name, level).is_none())
+                .unwrap_or(0),
+        };
+    }
 
-    let mut painter = {
-        let big_set = load_tileset(
-            &texture_creator,
-            "assets/image/tileset.png",
-            101,
-            171,
-            83,
-            40,
-        )?;
-        let small_set = load_tileset(
-            &texture_creator,
-            "assets/image/tileset-small.png",
-            50,
-            85,
-            41,
-            20,
-        )?;
-        let font = ttf_context.load_font("assets/font/RujisHandwritingFontv.2.0.ttf", 20)?;
-        Painter::new(&mut canvas, big_set, small_set, font)
+    let notice = if collection.skipped.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "{} invalid levels skipped",
+            collection.skipped.len()
+        ))
     };
 
-    mainloop(&sdl, levels.iter(), &mut painter, &mut canvas);
+    // Play in a window, unless the terminal is asked for or there is no display
+    #[cfg(feature = "sdl2")]
+    {
+        let video = if matches.is_present("tty") {
+            None
+        } else {
+            sdl2::init().and_then(|sdl| sdl.video()).ok()
+        };
+        if let Some(video) = video {
+            let width = value_t!(matches.value_of("width"), u32).unwrap_or(1024);
+            let height = value_t!(matches.value_of("height"), u32).unwrap_or(768);
+            let fullscreen = matches.is_present("fullscreen");
 
-    Ok(())
-}
+            // Initialize SDL components
+            let _ = sdl2::image::init(InitFlag::PNG)?;
+            let ttf_context = sdl2::ttf::init()?;
 
-/// Builds levels from a level collection file in the SLC format.
-fn load_slc_file<P: AsRef<Path>>(path: P) -> Result<Vec<Level>, error::SokobanError> {
-    let mut levels = Vec::new();
+            let mut window = create_window(&video, width, height, fullscreen)?;
+            if !collection.title.is_empty() {
+                window.set_title(&format!("sokoban-rs - {}", collection.title))?;
+            }
+            let mut canvas = window.into_canvas().build()?;
+            let texture_creator = canvas.texture_creator();
 
-    let parser = {
-        let file = File::open(path.as_ref())?;
-        EventReader::new(BufReader::new(file))
-    };
+            let mut painter = create_painter(&mut canvas, &texture_creator, &ttf_context)?;
+            if let Some(notice) = notice {
+                painter.set_notice(notice);
+            }
 
-    let mut level_title = String::new();
-    let mut level_data = String::new();
-    let mut reading_level = false;
-    for event in parser {
-        match event {
-            Ok(XmlEvent::StartElement {
-                ref name,
-                ref attributes,
-                ..
-            }) => {
-                if name.local_name == "L" {
-                    reading_level = true;
-                } else if name.local_name == "Level" {
-                    if let Some(id) = attributes.iter().find(|&attr| attr.name.local_name == "Id") {
-                        level_title = id.value.clone();
-                    }
-                }
+            if matches.is_present("animation") {
+                let ms = value_t!(matches.value_of("animation"), u64)?;
+                painter.set_move_duration(Duration::from_millis(ms));
             }
-            Ok(XmlEvent::EndElement { name }) => {
-                if name.local_name == "Level" {
-                    let mut level = Level::from_str(&level_data)?;
-                    level.set_title(level_title.clone());
-                    levels.push(level);
-                    level_data.clear();
+
+            let mut events = video.sdl().event_pump()?;
+            match editor {
+                Some(editor) => {
+                    editloop(
+                        &mut events,
+                        editor,
+                        Some(Path::new(slc_file)),
+                        None,
+                        &mut painter,
+                        &mut canvas,
+                    );
                 }
-            }
-            Ok(XmlEvent::Characters(ref data)) => {
-                if reading_level {
-                    level_data.push_str(data);
-                    level_data.push('\n');
+                None => {
+                    let mut game = Game {
+                        levels: collection.levels,
+                        name: &name,
+                        file: Some(Path::new(slc_file)),
+                        progress: &mut progress,
+                    };
+                    mainloop(&mut events, &mut game, start, &mut painter, &mut canvas);
                 }
             }
-            _ => {}
+            return Ok(());
         }
     }
 
-    Ok(levels)
+    // Play in the terminal
+    if editor.is_some() {
+        return Err("the level editor needs a display".into());
+    }
+    let mut game = Game {
+        levels: collection.levels,
+        name: &name,
+        file: Some(Path::new(slc_file)),
+        progress: &mut progress,
+    };
+    tty::mainloop(&mut game, start, notice)?;
+    Ok(())
 }
 
 /// Creates the SDL window
+#[cfg(feature = "sdl2")]
 fn create_window(
-    sdl: &Sdl,
+    video: &VideoSubsystem,
     width: u32,
     height: u32,
     fullscreen: bool,
 ) -> Result<Window, Box<dyn Error>> {
-    let mut window_builder = sdl.video()?.window("sokoban-rs", width, height);
+    let mut window_builder = video.window("sokoban-rs", width, height);
     if fullscreen {
         window_builder.fullscreen();
     } else {
@@ -160,9 +291,69 @@ fn create_window(
     Ok(window)
 }
 
+/// Creates a painter with the game's tilesets and font
+#[cfg(feature = "sdl2")]
+fn create_painter<'a, T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    texture_creator: &'a TextureCreator<T::Context>,
+    ttf_context: &'a Sdl2TtfContext,
+) -> Result<Painter<'a, T>, Box<dyn Error>> {
+    let big_set = load_tileset(
+        texture_creator,
+        "assets/image/tileset.png",
+        101,
+        171,
+        83,
+        40,
+    )?;
+    let small_set = load_tileset(
+        texture_creator,
+        "assets/image/tileset-small.png",
+        50,
+        85,
+        41,
+        20,
+    )?;
+    let font = ttf_context.load_font("assets/font/RujisHandwritingFontv.2.0.ttf", 20)?;
+    Ok(Painter::new(
+        canvas,
+        texture_creator,
+        big_set,
+        small_set,
+        font,
+    ))
+}
+
+/// Renders a level of a collection to a PNG file, without opening a window.
+///
+/// Drawing is done by the software renderer onto a surface, so that no
+/// display or GPU is needed.
+#[cfg(feature = "sdl2")]
+fn render(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = matches.value_of("input").unwrap();
+    let output = matches.value_of("output").unwrap();
+    let spec = matches.value_of("level").unwrap();
+    let collection = commands::load_collection(input, matches)?;
+    let level = collection
+        .find_level(spec)
+        .map(|index| &collection.levels[index])
+        .ok_or_else(|| format!("no level {} in {}", spec, input))?;
+
+    let _ = sdl2::image::init(InitFlag::PNG)?;
+    let ttf_context = sdl2::ttf::init()?;
+    let mut canvas = Surface::new(1, 1, PixelFormatEnum::RGBA8888)?.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let mut painter = create_painter(&mut canvas, &texture_creator, &ttf_context)?;
+
+    let image = painter.render(&mut canvas, level)?;
+    image.save(output)?;
+    Ok(())
+}
+
 /// Loads a tileset
-fn load_tileset<P: AsRef<Path>>(
-    texture_creator: &TextureCreator<WindowContext>,
+#[cfg(feature = "sdl2")]
+fn load_tileset<T, P: AsRef<Path>>(
+    texture_creator: &TextureCreator<T>,
     path: P,
     width: u32,
     height: u32,
@@ -174,45 +365,127 @@ fn load_tileset<P: AsRef<Path>>(
     Ok(tileset)
 }
 
+/// Represents the level collection being played
+pub struct Game<'a> {
+    /// The levels of the collection
+    levels: Vec<Level>,
+    /// The name under which the progress is recorded
+    name: &'a str,
+    /// The file where edited levels are saved, if any
+    file: Option<&'a Path>,
+    /// The player's progress
+    progress: &'a mut Progress,
+}
+
+impl<'a> Game<'a> {
+    /// Returns the best moves and pushes recorded for a level, if it was solved.
+    fn best_score(&self, level: &Level) -> Option<(usize, usize)> {
+        self.progress
+            .record(self.name, level)
+            .map(|record| (record.best_moves.moves, record.best_pushes.pushes))
+    }
+}
+
 /// Main game event loop
-fn mainloop<'a, I: Iterator<Item = &'a Level>>(
-    sdl: &Sdl,
-    mut levels: I,
-    painter: &mut Painter,
+///
+/// Returns true if the window was closed.
+#[cfg(feature = "sdl2")]
+fn mainloop(
+    events: &mut EventPump,
+    game: &mut Game,
+    start: usize,
+    painter: &mut Painter<Window>,
     canvas: &mut Canvas<Window>,
-) {
-    let (mut reference_level, mut level) = match levels.next() {
-        Some(l) => (l, l.clone()),
-        None => {
-            return;
-        }
-    };
+) -> bool {
+    if game.levels.is_empty() {
+        return false;
+    }
+    let mut index = start.min(game.levels.len() - 1);
+    let mut level = game.levels[index].clone();
+    painter.set_best(game.best_score(&game.levels[index]));
 
-    let mut running = true;
-    let mut events = sdl.event_pump().unwrap();
-    let mut skip = false;
-    while running {
-        if level.is_completed() || skip {
-            match levels.next() {
-                Some(l) => {
-                    reference_level = l;
-                    level = l.clone();
-                    skip = false;
-                }
-                None => {
-                    break;
-                }
+    let mut animator = Animator::new(&level);
+    let mut dragged_box = None;
+    let mut recorded = false;
+    loop {
+        // Record the solution as soon as the level is completed, then go to
+        // the next level once the last moves are shown
+        let animation = animator.tick(painter.move_duration());
+        if !level.is_completed() {
+            recorded = false;
+        } else if !recorded {
+            game.progress.update(game.name, &game.levels[index], &level);
+            if let Err(err) = game.progress.save() {
+                eprintln!("sokoban-rs: cannot save progress: {}", err);
             }
+            recorded = true;
+        }
+        if level.is_completed() && animation.is_none() {
+            if index + 1 == game.levels.len() {
+                return false;
+            }
+            index += 1;
+            level = game.levels[index].clone();
+            animator.reset(&level);
+            painter.set_best(game.best_score(&game.levels[index]));
+            dragged_box = None;
+            recorded = false;
         }
 
-        painter.paint(canvas, &level);
+        painter.set_animation(animation);
+        painter.paint(canvas, animator.shown());
 
-        match events.wait_event() {
-            Event::Quit { .. }
-            | Event::KeyDown {
+        // Keep painting frames while moves are being shown
+        let event = if animator.is_running() {
+            match events.wait_event_timeout(FRAME_TIME) {
+                Some(event) => event,
+                None => continue,
+            }
+        } else {
+            events.wait_event()
+        };
+        if let Event::KeyDown { .. } | Event::MouseButtonDown { .. } = event {
+            painter.clear_notice();
+        }
+
+        match event {
+            Event::Quit { .. } => return true,
+            Event::KeyDown {
                 keycode: Some(Keycode::Escape),
                 ..
-            } => running = false,
+            } => return false,
+            // The completed level no longer changes while its last moves are shown
+            Event::KeyDown {
+                keycode: Some(Keycode::Left),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Right),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Up),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Down),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::U),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Z),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Y),
+                ..
+            }
+            | Event::MouseButtonDown { .. }
+            | Event::MouseButtonUp { .. }
+                if level.is_completed() => {}
             Event::KeyDown {
                 keycode: Some(Keycode::Left),
                 ..
@@ -237,19 +510,390 @@ fn mainloop<'a, I: Iterator<Item = &'a Level>>(
             } => {
                 level.step(Direction::Down);
             }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let Some(pos) = painter.get_position(&level, x, y) {
+                    if level.is_box(&pos) {
+                        dragged_box = Some(pos);
+                    } else {
+                        level.walk_to(&pos);
+                    }
+                }
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let (Some(from), Some(to)) =
+                    (dragged_box.take(), painter.get_position(&level, x, y))
+                {
+                    if from != to && !level.push_box_to(&from, &to) {
+                        painter.set_notice("no route for this box");
+                    }
+                }
+            }
             Event::KeyDown {
                 keycode: Some(Keycode::R),
                 ..
             } => {
-                level = reference_level.clone();
+                level = game.levels[index].clone();
+                animator.reset(&level);
             }
             Event::KeyDown {
                 keycode: Some(Keycode::N),
                 ..
             } => {
-                skip = true;
+                if index + 1 == game.levels.len() {
+                    return false;
+                }
+                index += 1;
+                level = game.levels[index].clone();
+                animator.reset(&level);
+                painter.set_best(game.best_score(&game.levels[index]));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::P),
+                ..
+            } if index > 0 => {
+                index -= 1;
+                level = game.levels[index].clone();
+                animator.reset(&level);
+                painter.set_best(game.best_score(&game.levels[index]));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::D),
+                ..
+            } => {
+                painter.toggle_dead_squares();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::L),
+                ..
+            } => match select_level(events, game, index, painter, canvas) {
+                Selection::Level(i) => {
+                    index = i;
+                    level = game.levels[index].clone();
+                    animator.reset(&level);
+                    painter.set_best(game.best_score(&game.levels[index]));
+                }
+                Selection::Cancel => {}
+                Selection::Quit => return true,
+            },
+            Event::KeyDown {
+                keycode: Some(Keycode::E),
+                ..
+            } if game.file.is_some() => {
+                let editor = Editor::from_level(&game.levels[index]);
+                if editloop(
+                    events,
+                    editor,
+                    game.file,
+                    Some(&mut game.levels),
+                    painter,
+                    canvas,
+                ) {
+                    return true;
+                }
+                painter.set_best(game.best_score(&game.levels[index]));
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::U),
+                ..
+            } => {
+                level.undo();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Z),
+                keymod,
+                ..
+            } if is_ctrl(keymod) => {
+                level.undo();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Y),
+                keymod,
+                ..
+            } if is_ctrl(keymod) => {
+                level.redo();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Minus),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::KpMinus),
+                ..
+            } => change_move_duration(painter, false),
+            Event::KeyDown {
+                keycode: Some(Keycode::Plus),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Equals),
+                ..
+            }
+            | Event::KeyDown {
+                keycode: Some(Keycode::KpPlus),
+                ..
+            } => change_move_duration(painter, true),
+            _ => {}
+        }
+        animator.follow(&level, painter.move_duration());
+    }
+}
+
+/// Makes the animation of moves slower or faster, down to no animation at all.
+#[cfg(feature = "sdl2")]
+fn change_move_duration(painter: &mut Painter<Window>, slower: bool) {
+    let step = Duration::from_millis(MOVE_DURATION_STEP);
+    let duration = painter.move_duration();
+    let duration = if slower {
+        cmp::min(duration + step, Duration::from_millis(MAX_MOVE_DURATION))
+    } else {
+        duration.checked_sub(step).unwrap_or_default()
+    };
+    painter.set_move_duration(duration);
+    if duration.as_millis() == 0 {
+        painter.set_notice("animations: off");
+    } else {
+        painter.set_notice(format!("animations: {} ms", duration.as_millis()));
+    }
+}
+
+/// Represents the outcome of the level select screen
+pub enum Selection {
+    /// A level was chosen
+    Level(usize),
+    /// The screen was closed without choosing a level
+    Cancel,
+    /// The game should quit
+    Quit,
+}
+
+/// Runs the level select screen, starting with the given level selected
+#[cfg(feature = "sdl2")]
+fn select_level(
+    events: &mut EventPump,
+    game: &Game,
+    current: usize,
+    painter: &mut Painter<Window>,
+    canvas: &mut Canvas<Window>,
+) -> Selection {
+    let levels = &game.levels;
+    let summaries: Vec<_> = levels
+        .iter()
+        .map(|level| LevelSummary {
+            level,
+            best: game.best_score(level),
+        })
+        .collect();
+    let last = levels.len() - 1;
+    let columns = painter.level_select_columns();
+    let page = columns * painter.level_select_rows();
+    let mut selected = current;
+    loop {
+        painter.paint_level_select(canvas, &summaries, selected);
+
+        match events.wait_event() {
+            Event::Quit { .. } => return Selection::Quit,
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Escape | Keycode::L => return Selection::Cancel,
+                Keycode::Return | Keycode::KpEnter => return Selection::Level(selected),
+                Keycode::Left => selected = selected.saturating_sub(1),
+                Keycode::Right => selected = (selected + 1).min(last),
+                Keycode::Up if selected >= columns => selected -= columns,
+                Keycode::Down if selected + columns <= last => selected += columns,
+                Keycode::PageUp => selected = selected.saturating_sub(page),
+                Keycode::PageDown => selected = (selected + page).min(last),
+                Keycode::Home => selected = 0,
+                Keycode::End => selected = last,
+                _ => {}
+            },
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => {
+                if let Some(index) = painter.get_level_at(levels.len(), x, y) {
+                    return Selection::Level(index);
+                }
+            }
+            Event::MouseMotion { x, y, .. } => {
+                if let Some(index) = painter.get_level_at(levels.len(), x, y) {
+                    selected = index;
+                }
+            }
+            Event::MouseWheel { y, .. } if y > 0 && selected >= columns => selected -= columns,
+            Event::MouseWheel { y, .. } if y < 0 && selected + columns <= last => {
+                selected += columns
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Level editor event loop, saving to the given file if any
+///
+/// The saved level is also added to the given levels, if any, so that it can
+/// be played at once. Returns true if the window was closed.
+#[cfg(feature = "sdl2")]
+fn editloop(
+    events: &mut EventPump,
+    mut editor: Editor,
+    file: Option<&Path>,
+    mut levels: Option<&mut Vec<Level>>,
+    painter: &mut Painter<Window>,
+    canvas: &mut Canvas<Window>,
+) -> bool {
+    let mut tool = Tool::Wall;
+    let mut cursor = Position::new(0, 0);
+    let mut saved = None;
+    let mut brush = None;
+    painter.set_notice("Enter: test, Ctrl+S: save, Esc: leave");
+    loop {
+        let level = editor.to_level();
+        painter.set_animation(None);
+        painter.paint_editor(canvas, &level, &cursor, &tool.to_string());
+
+        let event = events.wait_event();
+        if let Event::KeyDown { .. } = event {
+            painter.clear_notice();
+        }
+
+        let (cols, rows) = editor.extents();
+        match event {
+            Event::Quit { .. } => return true,
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                ..
+            } => match keycode {
+                Keycode::Escape => return false,
+                Keycode::Left | Keycode::Right | Keycode::Up | Keycode::Down
+                    if is_shift(keymod) =>
+                {
+                    let (dc, dr) = match keycode {
+                        Keycode::Left => (-1, 0),
+                        Keycode::Right => (1, 0),
+                        Keycode::Up => (0, -1),
+                        _ => (0, 1),
+                    };
+                    editor.resize((cols + dc, rows + dr));
+                }
+                Keycode::Left => cursor = cursor.neighbor(Direction::Left),
+                Keycode::Right => cursor = cursor.neighbor(Direction::Right),
+                Keycode::Up => cursor = cursor.neighbor(Direction::Up),
+                Keycode::Down => cursor = cursor.neighbor(Direction::Down),
+                Keycode::W => tool = Tool::Wall,
+                Keycode::F => tool = Tool::Floor,
+                Keycode::T => tool = Tool::Target,
+                Keycode::B => tool = Tool::Box,
+                Keycode::P => tool = Tool::Player,
+                Keycode::Space => {
+                    editor.apply(tool, &cursor);
+                }
+                Keycode::Delete | Keycode::Backspace => {
+                    editor.apply(Tool::Floor, &cursor);
+                }
+                Keycode::D => painter.toggle_dead_squares(),
+                Keycode::Return | Keycode::KpEnter => {
+                    let problems = level.validate();
+                    if let Some(problem) = problems.first() {
+                        painter.set_notice(format!("cannot play: {}", problem));
+                    } else {
+                        let mut progress = Progress::default();
+                        let mut game = Game {
+                            levels: vec![level],
+                            name: "",
+                            file: None,
+                            progress: &mut progress,
+                        };
+                        if mainloop(events, &mut game, 0, painter, canvas) {
+                            return true;
+                        }
+                    }
+                }
+                Keycode::S if is_ctrl(keymod) => match file {
+                    Some(path) => match collection::save_level(path, &level, saved) {
+                        Ok(entry) => {
+                            saved = Some(entry);
+                            if let Some(ref mut levels) = levels {
+                                let mut level = level.clone();
+                                level.set_number(entry.number);
+                                // Saving again replaces the level added before
+                                match levels.iter().position(|l| l.number() == entry.number) {
+                                    Some(i) => levels[i] = level,
+                                    None => levels.push(level),
+                                }
+                            }
+                            painter.set_notice(format!(
+                                "saved as level {} of {}",
+                                entry.number,
+                                path.display()
+                            ));
+                        }
+                        Err(err) => painter.set_notice(format!("cannot save: {}", err)),
+                    },
+                    None => painter.set_notice("no file to save to"),
+                },
+                _ => {}
+            },
+            Event::MouseButtonDown {
+                mouse_btn, x, y, ..
+            } => {
+                brush = match mouse_btn {
+                    MouseButton::Left => Some(tool),
+                    MouseButton::Right => Some(Tool::Floor),
+                    _ => None,
+                };
+                if let (Some(brush), Some(pos)) = (brush, painter.get_position(&level, x, y)) {
+                    cursor = pos;
+                    editor.apply(brush, &pos);
+                }
+            }
+            Event::MouseMotion { x, y, .. } => {
+                if let Some(pos) = painter.get_position(&level, x, y) {
+                    // Only the player tool is not dragged, since there is a single player
+                    match brush {
+                        Some(brush) if brush != Tool::Player && pos != cursor => {
+                            editor.apply(brush, &pos);
+                        }
+                        _ => {}
+                    }
+                    cursor = pos;
+                }
             }
+            Event::MouseButtonUp { .. } => brush = None,
             _ => {}
         }
+
+        // Keep the cursor on the board
+        let (cols, rows) = editor.extents();
+        cursor = Position::new(
+            cmp::min(cmp::max(cursor.row(), 0), rows - 1),
+            cmp::min(cmp::max(cursor.column(), 0), cols - 1),
+        );
     }
 }
+
+/// Returns true if one of the Ctrl keys is pressed.
+#[cfg(feature = "sdl2")]
+fn is_ctrl(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+}
+
+/// Returns true if one of the Shift keys is pressed.
+#[cfg(feature = "sdl2")]
+fn is_shift(keymod: Mod) -> bool {
+    keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+}
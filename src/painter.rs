@@ -15,16 +15,21 @@
 
 use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
+use sdl2::surface::Surface;
 use sdl2::ttf::Font;
-use sdl2::video::Window;
+use std::collections::HashMap;
+use std::mem;
+use std::time::Duration;
 
-use game::{Direction, Level, Position};
+use game::{Direction, Level, Move, Position};
 use shadow::ShadowFlags;
 use tileset::{Tile, Tileset, TilesetSelector};
 
-/// The Painter struct is responsible for drawing the game onto the screen.
-pub struct Painter<'a> {
+/// The Painter struct is responsible for drawing the game onto the screen or into images.
+pub struct Painter<'a, T: RenderTarget> {
+    /// The creator of the textures used for off-screen rendering
+    creator: &'a TextureCreator<T::Context>,
     /// The tileset selector
     selector: TilesetSelector<'a>,
     /// The font used to display text
@@ -37,66 +42,495 @@ pub struct Painter<'a> {
     bar_color: Color,
     /// The color of the text in the status bar
     bar_text_color: Color,
+    /// Whether the dead squares are highlighted
+    show_dead_squares: bool,
+    /// The color used to highlight dead squares
+    dead_square_color: Color,
+    /// The color used to tint deadlocked boxes
+    deadlock_color: Color,
+    /// The color used to highlight the cursor of the editor
+    cursor_color: Color,
+    /// A message to display in the status bar
+    notice: Option<String>,
+    /// The best moves and pushes recorded for the current level
+    best: Option<(usize, usize)>,
+    /// The move being animated, if any
+    animation: Option<Animation>,
+    /// The duration of the animation of a move
+    move_duration: Duration,
+    /// The first row of levels shown on the level select screen
+    first_row: usize,
+    /// The image of the parts of the current level that do not move
+    static_layer: Option<StaticLayer<'a>>,
+    /// The off-screen buffer where the current level is drawn at full size
+    frame: Option<Texture<'a>>,
+    /// The thumbnails of the levels, keyed by board
+    thumbnails: HashMap<String, Texture<'a>>,
+    /// The rendered texts painted during the last frame, keyed by text and color
+    texts: TextCache<'a>,
+    /// The rendered texts painted during the current frame
+    texts_used: TextCache<'a>,
+}
+
+/// Rendered texts, keyed by text and color
+type TextCache<'a> = HashMap<(String, (u8, u8, u8, u8)), Texture<'a>>;
+
+/// Represents a cached image of the parts of a level that do not move:
+/// floor, targets, shadows, walls and highlighted dead squares.
+struct StaticLayer<'a> {
+    /// A description of what the image shows, to tell when it is out of date
+    key: String,
+    /// The full-size image
+    texture: Texture<'a>,
+}
+
+/// Represents a move being animated.
+///
+/// The level is painted in the state following the move, with the player and
+/// the pushed box drawn on their way from their previous positions.
+#[derive(Copy, Clone, Debug)]
+pub struct Animation {
+    /// The move being animated
+    pub step: Move,
+    /// The fraction of the move still to be done, from 1 at the start down to 0
+    pub remaining: f64,
+}
+
+/// Represents a level listed on the level select screen.
+pub struct LevelSummary<'l> {
+    /// The level in its initial state
+    pub level: &'l Level,
+    /// The best moves and pushes, if the level was solved
+    pub best: Option<(usize, usize)>,
 }
 
 /// Represents a location for text in the status bar
 #[derive(Clone, Copy)]
 enum StatusBarLocation {
     FlushLeft,
+    Centered,
     FlushRight,
 }
 
-impl<'a> Painter<'a> {
+impl<'a, T: RenderTarget> Painter<'a, T> {
+    /// The size of a cell on the level select screen
+    const CELL_SIZE: (u32, u32) = (256, 232);
+    /// The size of a thumbnail on the level select screen
+    const THUMBNAIL_SIZE: (u32, u32) = (240, 160);
+
     /// Creates a new instance.
     pub fn new(
-        canvas: &mut Canvas<Window>,
+        canvas: &mut Canvas<T>,
+        creator: &'a TextureCreator<T::Context>,
         big_set: Tileset<'a>,
         small_set: Tileset<'a>,
         font: Font<'a, 'a>,
-    ) -> Painter<'a> {
-        let screen_size = canvas.window().drawable_size();
+    ) -> Painter<'a, T> {
+        let screen_size = canvas.output_size().unwrap();
         let selector = TilesetSelector::new(big_set, small_set);
         Painter {
+            creator,
             selector,
             font,
             screen_size,
             bar_height: 32,
             bar_color: Color::RGBA(20, 20, 20, 255),
             bar_text_color: Color::RGBA(255, 192, 0, 255),
+            show_dead_squares: false,
+            dead_square_color: Color::RGBA(255, 0, 0, 96),
+            deadlock_color: Color::RGB(255, 96, 96),
+            cursor_color: Color::RGBA(255, 192, 0, 128),
+            notice: None,
+            best: None,
+            animation: None,
+            move_duration: Duration::from_millis(120),
+            first_row: 0,
+            static_layer: None,
+            frame: None,
+            thumbnails: HashMap::new(),
+            texts: HashMap::new(),
+            texts_used: HashMap::new(),
         }
     }
 
+    /// Displays a message in the status bar.
+    pub fn set_notice<S: Into<String>>(&mut self, notice: S) {
+        self.notice = Some(notice.into());
+    }
+
+    /// Removes the message displayed in the status bar.
+    pub fn clear_notice(&mut self) {
+        self.notice = None;
+    }
+
+    /// Sets the best moves and pushes to display for the current level.
+    pub fn set_best(&mut self, best: Option<(usize, usize)>) {
+        self.best = best;
+    }
+
+    /// Sets the move being animated, if any.
+    pub fn set_animation(&mut self, animation: Option<Animation>) {
+        self.animation = animation;
+    }
+
+    /// Returns the duration of the animation of a move.
+    pub fn move_duration(&self) -> Duration {
+        self.move_duration
+    }
+
+    /// Changes the duration of the animation of a move, zero disabling animations.
+    pub fn set_move_duration(&mut self, duration: Duration) {
+        self.move_duration = duration;
+    }
+
+    /// Toggles the highlighting of dead squares.
+    pub fn toggle_dead_squares(&mut self) {
+        self.show_dead_squares = !self.show_dead_squares;
+    }
+
     /// Paints a level onto the screen.
-    pub fn paint(&mut self, canvas: &mut Canvas<Window>, level: &Level) {
+    pub fn paint(&mut self, canvas: &mut Canvas<T>, level: &Level) {
+        self.paint_board(canvas, level, None);
+        self.paint_status_bar(canvas, level);
+        self.present(canvas);
+    }
+
+    /// Paints a level being edited onto the screen, with the cursor at the given position.
+    pub fn paint_editor(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        level: &Level,
+        cursor: &Position,
+        tool: &str,
+    ) {
+        self.paint_board(canvas, level, Some(cursor));
+
+        self.paint_status_background(canvas);
+        let (cols, rows) = level.extents();
+        let s = format!("tool: {}  size: {} x {}", tool, cols, rows);
+        self.paint_status_text(canvas, &s, StatusBarLocation::FlushLeft);
+        if let Some(notice) = self.notice.clone() {
+            self.paint_status_text(canvas, &notice, StatusBarLocation::Centered);
+        }
+        self.paint_status_text(canvas, level.title(), StatusBarLocation::FlushRight);
+
+        self.present(canvas);
+    }
+
+    /// Renders a full-size image of a level, as it is painted on the screen
+    /// without the status bar.
+    pub fn render(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        level: &Level,
+    ) -> Result<Surface<'static>, String> {
         self.selector.reset(level.extents());
+        let (w, h) = self.tileset().get_rendering_size(level.extents());
+        if w == 0 || h == 0 {
+            return Err("the level is empty".to_string());
+        }
+
+        let mut texture = self.create_target((w, h));
+        let mut pixels = Ok(Vec::new());
+        canvas
+            .with_texture_canvas(&mut texture, |cv| {
+                self.paint_static(cv, level);
+                self.paint_objects(cv, level);
+                pixels = cv.read_pixels(Rect::new(0, 0, w, h), PixelFormatEnum::ABGR8888);
+            })
+            .map_err(|e| e.to_string())?;
+        let pixels = pixels?;
 
-        // Draw a full-size image onto an off-screen buffer
+        // Copy the pixels row by row, since the surface may pad its rows
+        let mut surface = Surface::new(w, h, PixelFormatEnum::ABGR8888)?;
+        let pitch = surface.pitch() as usize;
+        let row = w as usize * 4;
+        surface.with_lock_mut(|data| {
+            for (y, line) in pixels.chunks(row).enumerate() {
+                data[y * pitch..y * pitch + row].copy_from_slice(line);
+            }
+        });
+        Ok(surface)
+    }
+
+    /// Returns the position of the level displayed at the given screen coordinates.
+    pub fn get_position(&self, level: &Level, x: i32, y: i32) -> Option<Position> {
         let fullsize = self.tileset().get_rendering_size(level.extents());
-        let creator = canvas.texture_creator();
-        let mut texture = creator
-            .create_texture_target(PixelFormatEnum::RGBA8888, fullsize.0, fullsize.1)
-            .expect("Could not get texture target for off-screen rendering");
+        let rect = self.get_centered_image_rect(self.get_scaled_rendering_size(level))?;
+        if !rect.contains_point((x, y)) {
+            return None;
+        }
+        let scale = |v: i32, full: u32, scaled: u32| {
+            (i64::from(v) * i64::from(full) / i64::from(scaled)) as i32
+        };
+        let pos = self.tileset().get_position(
+            scale(x - rect.x(), fullsize.0, rect.width()),
+            scale(y - rect.y(), fullsize.1, rect.height()),
+        );
+        if level.is_inside(&pos) {
+            Some(pos)
+        } else {
+            None
+        }
+    }
+
+    /// Paints the level select screen, with the given level highlighted.
+    pub fn paint_level_select(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        levels: &[LevelSummary],
+        selected: usize,
+    ) {
+        // Scroll so that the selected level is visible
+        let columns = self.level_select_columns();
+        let rows = self.level_select_rows();
+        let row = selected / columns;
+        if row < self.first_row {
+            self.first_row = row;
+        } else if row >= self.first_row + rows {
+            self.first_row = row + 1 - rows;
+        }
+
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.clear();
+
+        let first = self.first_row * columns;
+        let last = levels.len().min(first + rows * columns);
+        for (index, summary) in levels.iter().enumerate().take(last).skip(first) {
+            let cell = self.get_cell_rect(index);
+            let area = Self::get_thumbnail_rect(cell);
+            self.paint_thumbnail(canvas, summary.level, area);
+
+            let (x, y, w) = (area.x(), area.bottom(), area.width());
+            let title = format!("{}. {}", summary.level.number(), summary.level.title());
+            let color = self.bar_text_color;
+            self.paint_text(canvas, &title, color, x, y + 4, w);
+            let (status, color) = match summary.best {
+                Some((moves, pushes)) => (
+                    format!("solved, best: {} / {}", moves, pushes),
+                    Color::RGB(96, 224, 96),
+                ),
+                None => ("unsolved".to_string(), Color::RGB(160, 160, 160)),
+            };
+            self.paint_text(canvas, &status, color, x, y + 30, w);
+
+            if index == selected {
+                let prev_color = canvas.draw_color();
+                canvas.set_draw_color(self.bar_text_color);
+                canvas.draw_rect(cell).unwrap();
+                canvas.set_draw_color(prev_color);
+            }
+        }
+
+        self.paint_status_background(canvas);
+        // The number of the level in the file, as shown in its cell
+        let s = format!("level {}", levels[selected].level.number());
+        self.paint_status_text(canvas, &s, StatusBarLocation::FlushLeft);
+        let solved = levels.iter().filter(|l| l.best.is_some()).count();
+        let s = format!("solved: {} / {}", solved, levels.len());
+        self.paint_status_text(canvas, &s, StatusBarLocation::Centered);
+        self.paint_status_text(
+            canvas,
+            "Enter: play, Esc: back",
+            StatusBarLocation::FlushRight,
+        );
+
+        self.present(canvas);
+    }
+
+    /// Returns the number of levels per row on the level select screen.
+    pub fn level_select_columns(&self) -> usize {
+        (self.screen_size.0 / Self::CELL_SIZE.0).max(1) as usize
+    }
 
+    /// Returns the number of rows of levels visible on the level select screen.
+    pub fn level_select_rows(&self) -> usize {
+        ((self.screen_size.1 - self.bar_height) / Self::CELL_SIZE.1).max(1) as usize
+    }
+
+    /// Returns the index of the level displayed at the given screen coordinates
+    /// on the level select screen, among the given number of levels.
+    pub fn get_level_at(&self, count: usize, x: i32, y: i32) -> Option<usize> {
+        let columns = self.level_select_columns();
+        let first = self.first_row * columns;
+        let last = count.min(first + self.level_select_rows() * columns);
+        (first..last).find(|&index| self.get_cell_rect(index).contains_point((x, y)))
+    }
+
+    /// Returns the Rect of the cell of a level on the level select screen.
+    fn get_cell_rect(&self, index: usize) -> Rect {
+        let columns = self.level_select_columns();
+        let (w, h) = Self::CELL_SIZE;
+        let left = (self.screen_size.0.saturating_sub(columns as u32 * w) / 2) as i32;
+        let row = index / columns - self.first_row;
+        let col = index % columns;
+        Rect::new(
+            left + (col as u32 * w) as i32,
+            (row as u32 * h) as i32,
+            w,
+            h,
+        )
+    }
+
+    /// Returns the Rect where the thumbnail of a level is painted within its cell.
+    fn get_thumbnail_rect(cell: Rect) -> Rect {
+        let (w, h) = Self::THUMBNAIL_SIZE;
+        let margin = (cell.width() - w) as i32 / 2;
+        Rect::new(cell.x() + margin, cell.y() + margin, w, h)
+    }
+
+    /// Paints a thumbnail of a level with the small tileset, centered in the given area.
+    ///
+    /// Thumbnails are rendered once and kept for the next frames.
+    fn paint_thumbnail(&mut self, canvas: &mut Canvas<T>, level: &Level, area: Rect) {
+        let key = level.to_string();
+        if !self.thumbnails.contains_key(&key) {
+            match self.render_thumbnail(canvas, level, area) {
+                Some(thumbnail) => self.thumbnails.insert(key.clone(), thumbnail),
+                None => return,
+            };
+        }
+        let thumbnail = &self.thumbnails[&key];
+        let q = thumbnail.query();
+        let x = area.x() + (area.width() - q.width) as i32 / 2;
+        let y = area.y() + (area.height() - q.height) as i32 / 2;
         canvas
-            .with_texture_canvas(&mut texture, |cv| {
-                self.paint_fullsize(cv, level);
+            .copy(thumbnail, None, Some(Rect::new(x, y, q.width, q.height)))
+            .unwrap();
+    }
+
+    /// Renders a thumbnail of a level with the small tileset, scaled to fit in the given area.
+    fn render_thumbnail(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        level: &Level,
+        area: Rect,
+    ) -> Option<Texture<'a>> {
+        self.selector.reset(level.extents());
+        self.selector.force_small(true);
+        let show_dead_squares = self.show_dead_squares;
+        self.show_dead_squares = false;
+        let animation = self.animation.take();
+
+        let fullsize = self.tileset().get_rendering_size(level.extents());
+        let mut thumbnail = None;
+        if fullsize.0 > 0 && fullsize.1 > 0 {
+            let mut texture = self.create_target(fullsize);
+            canvas
+                .with_texture_canvas(&mut texture, |cv| {
+                    self.paint_static(cv, level);
+                    self.paint_objects(cv, level);
+                })
+                .unwrap();
+
+            let ratio = f64::min(
+                1.0,
+                f64::min(
+                    f64::from(area.width()) / f64::from(fullsize.0),
+                    f64::from(area.height()) / f64::from(fullsize.1),
+                ),
+            );
+            let scale = |sz: u32| ((ratio * f64::from(sz)).floor() as u32).max(1);
+            let mut scaled = self.create_target((scale(fullsize.0), scale(fullsize.1)));
+            canvas
+                .with_texture_canvas(&mut scaled, |cv| {
+                    cv.copy(&texture, None, None).unwrap();
+                })
+                .unwrap();
+            thumbnail = Some(scaled);
+        }
+
+        self.show_dead_squares = show_dead_squares;
+        self.animation = animation;
+        self.selector.force_small(false);
+        thumbnail
+    }
+
+    /// Paints a level scaled to fit above the status bar, highlighting the cursor if any.
+    ///
+    /// The parts of the level that do not move are kept in a static layer,
+    /// which is only redrawn when they change.
+    fn paint_board(&mut self, canvas: &mut Canvas<T>, level: &Level, cursor: Option<&Position>) {
+        self.selector.reset(level.extents());
+        let fullsize = self.tileset().get_rendering_size(level.extents());
+        if fullsize.0 == 0 || fullsize.1 == 0 {
+            canvas.set_draw_color(Color::RGB(0, 0, 0));
+            canvas.clear();
+            return;
+        }
+
+        // Redraw the static layer if the level or the display options changed
+        let key = self.static_key(level);
+        let up_to_date = match self.static_layer {
+            Some(ref layer) => layer.key == key,
+            None => false,
+        };
+        if !up_to_date {
+            let mut texture = match self.static_layer.take() {
+                Some(layer) if texture_size(&layer.texture) == fullsize => layer.texture,
+                _ => self.create_target(fullsize),
+            };
+            canvas
+                .with_texture_canvas(&mut texture, |cv| {
+                    self.paint_static(cv, level);
+                })
+                .unwrap();
+            self.static_layer = Some(StaticLayer { key, texture });
+        }
+
+        // Draw the moving objects over the static layer onto an off-screen buffer
+        let mut frame = match self.frame.take() {
+            Some(frame) if texture_size(&frame) == fullsize => frame,
+            _ => self.create_target(fullsize),
+        };
+        canvas
+            .with_texture_canvas(&mut frame, |cv| {
+                if let Some(ref layer) = self.static_layer {
+                    cv.copy(&layer.texture, None, None).unwrap();
+                }
+                self.paint_objects(cv, level);
+                if let Some(pos) = cursor {
+                    let (x, y) = self.tileset().get_coordinates(pos);
+                    let color = self.cursor_color;
+                    self.paint_overlay(cv, color, x, y);
+                }
             })
             .unwrap();
 
         // Copy onto the screen with appropriate scaling
-        let final_rect = self.get_centered_image_rect(self.get_scaled_rendering_size(&level));
+        let final_rect = self.get_centered_image_rect(self.get_scaled_rendering_size(level));
 
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         let original_rect = Some(Rect::new(0, 0, fullsize.0, fullsize.1));
-        canvas.copy(&texture, original_rect, final_rect).unwrap();
-
-        self.paint_status_bar(canvas, &level);
+        canvas.copy(&frame, original_rect, final_rect).unwrap();
+        self.frame = Some(frame);
+    }
 
-        canvas.present();
+    /// Returns a description of what the static layer of a level shows.
+    fn static_key(&self, level: &Level) -> String {
+        let (cols, rows) = level.extents();
+        let mut key = format!("{}x{}/{}:", cols, rows, self.tileset().width());
+        for r in 0..rows {
+            for c in 0..cols {
+                let pos = Position::new(r, c);
+                key.push(if level.is_wall(&pos) {
+                    '#'
+                } else if level.is_square(&pos) {
+                    '.'
+                } else if self.show_dead_squares && level.is_dead_square(&pos) {
+                    'x'
+                } else {
+                    ' '
+                });
+            }
+        }
+        key
     }
 
-    /// Paints a full-size image of the given level onto the current render target.
-    fn paint_fullsize(&mut self, canvas: &mut Canvas<Window>, level: &Level) {
+    /// Paints the parts of the given level that do not move onto the current
+    /// render target: floor, targets, shadows and walls.
+    fn paint_static(&mut self, canvas: &mut Canvas<T>, level: &Level) {
         let (cols, rows) = level.extents();
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
@@ -113,8 +547,13 @@ impl<'a> Painter<'a> {
                     self.paint_tile(canvas, Tile::Floor, x, y);
                 }
 
+                // Highlight the dead squares
+                if self.show_dead_squares && level.is_dead_square(&pos) {
+                    self.paint_overlay(canvas, self.dead_square_color, x, y);
+                }
+
                 // Add the shadows
-                let flags = get_shadow_flags(&level, &pos);
+                let flags = get_shadow_flags(level, &pos);
                 for f in &[
                     ShadowFlags::N_EDGE,
                     ShadowFlags::S_EDGE,
@@ -130,23 +569,125 @@ impl<'a> Painter<'a> {
                     }
                 }
 
-                // Draw the other items
-                let z = y - self.tileset().offset();
+                // Draw the walls
                 if level.is_wall(&pos) {
+                    let z = y - self.tileset().offset();
                     self.paint_tile(canvas, Tile::Wall, x, z);
                 }
+            }
+        }
+    }
+
+    /// Paints the boxes and the player of the given level over its static layer.
+    ///
+    /// Items are taller than a row, so the walls of the rows in front of each
+    /// object are painted again to cover it as they would in a full redraw.
+    fn paint_objects(&mut self, canvas: &mut Canvas<T>, level: &Level) {
+        let (cols, rows) = level.extents();
+        let overlap = ((self.tileset().height() - 1) / self.tileset().effective_height()) as i32;
+        for r in 0..rows {
+            for c in 0..cols {
+                let pos = Position::new(r, c);
+                if !level.is_box(&pos) && !level.is_player(&pos) {
+                    continue;
+                }
+                let (dx, dy) = self.get_animation_offset(level, &pos);
+                let (x, y) = self.tileset().get_coordinates(&pos);
+                let (x, y) = (x + dx, y + dy);
+                let z = y - self.tileset().offset();
                 if level.is_box(&pos) {
-                    self.paint_tile(canvas, Tile::Rock, x, z);
+                    if level.is_deadlocked_box(&pos) {
+                        let color = self.deadlock_color;
+                        self.set_tint(color);
+                        self.paint_tile(canvas, Tile::Rock, x, z);
+                        self.set_tint(Color::RGB(255, 255, 255));
+                    } else {
+                        self.paint_tile(canvas, Tile::Rock, x, z);
+                    }
                 }
                 if level.is_player(&pos) {
                     self.paint_tile(canvas, Tile::Player, x, z);
                 }
+
+                // An object on its way covers one more row or column
+                let last_row = r + overlap + if dy > 0 { 1 } else { 0 };
+                let columns = [c, c + dx.signum()];
+                let columns = if dx == 0 { &columns[..1] } else { &columns[..] };
+                for fr in r + 1..=last_row {
+                    for &fc in columns {
+                        let front = Position::new(fr, fc);
+                        if level.is_wall(&front) {
+                            let (x, y) = self.tileset().get_coordinates(&front);
+                            let z = y - self.tileset().offset();
+                            self.paint_tile(canvas, Tile::Wall, x, z);
+                        }
+                    }
+                }
             }
         }
     }
 
+    /// Returns the offset in pixels at which an object must be drawn from its
+    /// position, if it is being animated.
+    fn get_animation_offset(&self, level: &Level, pos: &Position) -> (i32, i32) {
+        let animation = match self.animation {
+            Some(animation) => animation,
+            None => return (0, 0),
+        };
+        let dir = animation.step.direction();
+        let player = level.player();
+        if *pos != player && !(animation.step.is_push() && *pos == player.neighbor(dir)) {
+            return (0, 0);
+        }
+        let (dc, dr) = match dir {
+            Direction::Left => (-1.0, 0.0),
+            Direction::Right => (1.0, 0.0),
+            Direction::Up => (0.0, -1.0),
+            Direction::Down => (0.0, 1.0),
+        };
+        let width = f64::from(self.tileset().width());
+        let height = f64::from(self.tileset().effective_height());
+        (
+            -(dc * width * animation.remaining).round() as i32,
+            -(dr * height * animation.remaining).round() as i32,
+        )
+    }
+
     /// Paints the status bar
-    fn paint_status_bar(&mut self, canvas: &mut Canvas<Window>, level: &Level) {
+    fn paint_status_bar(&mut self, canvas: &mut Canvas<T>, level: &Level) {
+        self.paint_status_background(canvas);
+
+        // Paints the number of moves and pushes, along with the best ones
+        let mut s = format!(
+            "moves / pushes: {} / {}",
+            level.get_steps(),
+            level.get_pushes()
+        );
+        if let Some((moves, pushes)) = self.best {
+            s.push_str(&format!("  (best: {} / {})", moves, pushes));
+        }
+        self.paint_status_text(canvas, &s, StatusBarLocation::FlushLeft);
+
+        // Paints the notice, or warns about boxes that can no longer reach targets
+        if let Some(notice) = self.notice.clone() {
+            self.paint_status_text(canvas, &notice, StatusBarLocation::Centered);
+        } else if level.has_dead_box() {
+            self.paint_status_text(canvas, "dead box!", StatusBarLocation::Centered);
+        } else if level.is_deadlocked() {
+            self.paint_status_text(canvas, "deadlock!", StatusBarLocation::Centered);
+        }
+
+        // Paints the level's title and author
+        let s = if level.copyright().is_empty() {
+            level.title().to_string()
+        } else {
+            format!("{} by {}", level.title(), level.copyright())
+        };
+        self.paint_status_text(canvas, &s, StatusBarLocation::FlushRight);
+    }
+
+    /// Paints the background of the status bar
+    fn paint_status_background(&mut self, canvas: &mut Canvas<T>) {
         let prev_color = canvas.draw_color();
         canvas.set_draw_color(self.bar_color);
         let rect = Rect::new(
@@ -157,46 +698,100 @@ impl<'a> Painter<'a> {
         );
         canvas.fill_rect(rect).unwrap();
         canvas.set_draw_color(prev_color);
-
-        // Paints the number of moves
-        let s = format!("# moves: {}", level.get_steps());
-        self.paint_status_text(canvas, &s, StatusBarLocation::FlushLeft);
-
-        // Paints the level's title
-        self.paint_status_text(canvas, level.title(), StatusBarLocation::FlushRight);
     }
 
     /// Paints text in the status bar
     fn paint_status_text(
         &mut self,
-        canvas: &mut Canvas<Window>,
+        canvas: &mut Canvas<T>,
         text: &str,
         location: StatusBarLocation,
     ) {
-        let surface = self.font.render(text).blended(self.bar_text_color).unwrap();
-        let creator = canvas.texture_creator();
-        let texture = creator.create_texture_from_surface(&surface).unwrap();
         let margin = 4;
-        let (w, h) = {
-            let q = texture.query();
-            (q.width, q.height)
+        let (w, h) = match self.text_size(text, self.bar_text_color) {
+            Some(size) => size,
+            None => return,
         };
-        let (x, y) = match location {
-            StatusBarLocation::FlushLeft => {
-                (margin as i32, (self.screen_size.1 - margin - h) as i32)
-            }
-            StatusBarLocation::FlushRight => (
-                (self.screen_size.0 - margin - w) as i32,
-                (self.screen_size.1 - margin - h) as i32,
-            ),
+        // Text wider than the bar is cut at its right edge
+        let (width, height) = self.screen_size;
+        let w = w.min(width.saturating_sub(2 * margin));
+        if w == 0 {
+            return;
+        }
+        let y = height.saturating_sub(margin + h) as i32;
+        let x = match location {
+            StatusBarLocation::FlushLeft => margin,
+            StatusBarLocation::Centered => width.saturating_sub(w) / 2,
+            StatusBarLocation::FlushRight => width.saturating_sub(margin + w),
+        } as i32;
+        let color = self.bar_text_color;
+        self.paint_text(canvas, text, color, x, y, w);
+    }
+
+    /// Paints text at the given coordinates, cut at the given width.
+    fn paint_text(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        text: &str,
+        color: Color,
+        x: i32,
+        y: i32,
+        max_width: u32,
+    ) {
+        let (w, h) = match self.text_size(text, color) {
+            Some((w, h)) => (w.min(max_width), h),
+            None => return,
         };
+        let texture = &self.texts_used[&(text.to_string(), color.rgba())];
         canvas
-            .copy(&texture, None, Some(Rect::new(x, y, w, h)))
+            .copy(
+                texture,
+                Some(Rect::new(0, 0, w, h)),
+                Some(Rect::new(x, y, w, h)),
+            )
             .unwrap();
     }
 
+    /// Returns the size of a text once rendered, or None if it is empty.
+    ///
+    /// The rendered text is kept for the current frame, reusing the one from
+    /// the last frame if possible.
+    fn text_size(&mut self, text: &str, color: Color) -> Option<(u32, u32)> {
+        if text.is_empty() {
+            return None;
+        }
+        let key = (text.to_string(), color.rgba());
+        let texture = match self.texts_used.remove(&key) {
+            Some(texture) => texture,
+            None => match self.texts.remove(&key) {
+                Some(texture) => texture,
+                None => {
+                    let surface = self.font.render(text).blended(color).unwrap();
+                    self.creator.create_texture_from_surface(&surface).unwrap()
+                }
+            },
+        };
+        let size = texture_size(&texture);
+        self.texts_used.insert(key, texture);
+        Some(size)
+    }
+
+    /// Shows the painted frame on the screen, dropping the rendered texts
+    /// that were not used during the frame.
+    fn present(&mut self, canvas: &mut Canvas<T>) {
+        canvas.present();
+        self.texts = mem::take(&mut self.texts_used);
+    }
+
+    /// Creates a texture that can be used as a render target.
+    fn create_target(&self, size: (u32, u32)) -> Texture<'a> {
+        self.creator
+            .create_texture_target(PixelFormatEnum::RGBA8888, size.0, size.1)
+            .expect("Could not get texture target for off-screen rendering")
+    }
+
     /// Paints a tile at the given coordinates.
-    fn paint_tile(&mut self, canvas: &mut Canvas<Window>, tile: Tile, x: i32, y: i32) {
+    fn paint_tile(&mut self, canvas: &mut Canvas<T>, tile: Tile, x: i32, y: i32) {
         let (col, row) = self.tileset().location(tile).unwrap_or_else(|| {
             panic!("No image for this tile: {:?}", tile);
         });
@@ -212,6 +807,31 @@ impl<'a> Painter<'a> {
             .unwrap();
     }
 
+    /// Changes the color modulation of the current tileset.
+    fn set_tint(&mut self, color: Color) {
+        self.selector
+            .select_mut()
+            .texture_mut()
+            .set_color_mod(color.r, color.g, color.b);
+    }
+
+    /// Paints a translucent rectangle over the floor tile at the given coordinates.
+    fn paint_overlay(&mut self, canvas: &mut Canvas<T>, color: Color, x: i32, y: i32) {
+        let rect = Rect::new(
+            x,
+            y + self.tileset().offset(),
+            self.tileset().width(),
+            self.tileset().effective_height(),
+        );
+        let prev_color = canvas.draw_color();
+        let prev_mode = canvas.blend_mode();
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(color);
+        canvas.fill_rect(rect).unwrap();
+        canvas.set_blend_mode(prev_mode);
+        canvas.set_draw_color(prev_color);
+    }
+
     /// Returns the size of the drawing scaled to fit onto the screen.
     fn get_scaled_rendering_size(&self, level: &Level) -> (u32, u32) {
         let render_size = self.tileset().get_rendering_size(level.extents());
@@ -237,6 +857,12 @@ impl<'a> Painter<'a> {
     }
 }
 
+/// Returns the width and height of a texture.
+fn texture_size(texture: &Texture) -> (u32, u32) {
+    let q = texture.query();
+    (q.width, q.height)
+}
+
 /// Returns the shadow flags for a particular position in the given level.
 fn get_shadow_flags(level: &Level, pos: &Position) -> ShadowFlags {
     let north = pos.neighbor(Direction::Up);
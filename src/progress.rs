@@ -0,0 +1,218 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The player's progress, saved between runs.
+//!
+//! The save file holds one line per solved level, with tab-separated fields:
+//! the collection, the level's fingerprint, then the moves, pushes and LURD
+//! solution of the best solution by moves and of the best solution by pushes.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use error::SokobanError;
+use game::Level;
+
+/// Represents a solution of a level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Score {
+    /// The number of moves
+    pub moves: usize,
+    /// The number of pushes
+    pub pushes: usize,
+    /// The solution in LURD notation
+    pub lurd: String,
+}
+
+impl Score {
+    /// Returns the score of a completed level.
+    fn of(level: &Level) -> Score {
+        Score {
+            moves: level.get_steps() as usize,
+            pushes: level.get_pushes() as usize,
+            lurd: level.to_lurd(),
+        }
+    }
+}
+
+/// Represents the best solutions of a solved level.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The solution with the fewest moves, then the fewest pushes
+    pub best_moves: Score,
+    /// The solution with the fewest pushes, then the fewest moves
+    pub best_pushes: Score,
+}
+
+/// Keeps track of the solved levels and their best solutions.
+///
+/// The default value has no record and is never saved.
+#[derive(Default)]
+pub struct Progress {
+    /// The save file, if any
+    path: Option<PathBuf>,
+    /// The records, keyed by collection and level fingerprint
+    records: HashMap<(String, String), Record>,
+}
+
+impl Progress {
+    /// Loads the progress from the save file in the XDG data directory.
+    ///
+    /// A missing save file means no level has been solved yet. Lines that
+    /// cannot be read are ignored.
+    pub fn load() -> Result<Progress, SokobanError> {
+        let path = save_file();
+        let mut progress = Progress {
+            path: path.clone(),
+            records: HashMap::new(),
+        };
+        let data = match path {
+            Some(path) => match fs::read(path) {
+                Ok(data) => data,
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(progress),
+                Err(err) => return Err(err.into()),
+            },
+            None => return Ok(progress),
+        };
+
+        for line in String::from_utf8_lossy(&data).lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 8 {
+                continue;
+            }
+            let score = |i: usize| {
+                Some(Score {
+                    moves: fields[i].parse().ok()?,
+                    pushes: fields[i + 1].parse().ok()?,
+                    lurd: fields[i + 2].to_string(),
+                })
+            };
+            if let (Some(best_moves), Some(best_pushes)) = (score(2), score(5)) {
+                let key = (fields[0].to_string(), fields[1].to_string());
+                progress.records.insert(
+                    key,
+                    Record {
+                        best_moves,
+                        best_pushes,
+                    },
+                );
+            }
+        }
+        Ok(progress)
+    }
+
+    /// Writes the progress to the save file.
+    ///
+    /// The records are first written to a temporary file, which then replaces
+    /// the save file, so that an interrupted save loses nothing.
+    pub fn save(&self) -> Result<(), SokobanError> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut keys: Vec<_> = self.records.keys().collect();
+        keys.sort();
+        let temporary = path.with_extension("txt.tmp");
+        let mut out = BufWriter::new(File::create(&temporary)?);
+        for key in keys {
+            let record = &self.records[key];
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                key.0,
+                key.1,
+                record.best_moves.moves,
+                record.best_moves.pushes,
+                record.best_moves.lurd,
+                record.best_pushes.moves,
+                record.best_pushes.pushes,
+                record.best_pushes.lurd
+            )?;
+        }
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Returns the record of a level, given in its initial state, if it was solved.
+    pub fn record(&self, collection: &str, level: &Level) -> Option<&Record> {
+        self.records.get(&key(collection, level))
+    }
+
+    /// Records the solution of a completed level, given the level in its
+    /// initial state.
+    ///
+    /// Returns true if the solution improves on the best ones.
+    pub fn update(&mut self, collection: &str, initial: &Level, completed: &Level) -> bool {
+        let score = Score::of(completed);
+        if let Some(record) = self.records.get_mut(&key(collection, initial)) {
+            let mut improved = false;
+            if (score.moves, score.pushes) < (record.best_moves.moves, record.best_moves.pushes) {
+                record.best_moves = score.clone();
+                improved = true;
+            }
+            if (score.pushes, score.moves) < (record.best_pushes.pushes, record.best_pushes.moves) {
+                record.best_pushes = score;
+                improved = true;
+            }
+            return improved;
+        }
+        self.records.insert(
+            key(collection, initial),
+            Record {
+                best_moves: score.clone(),
+                best_pushes: score,
+            },
+        );
+        true
+    }
+}
+
+/// Returns the key of a level, given in its initial state.
+fn key(collection: &str, level: &Level) -> (String, String) {
+    let collection = collection.replace(&['\t', '\n'][..], " ");
+    (collection, fingerprint(level))
+}
+
+/// Returns a fingerprint of the board of a level, given in its initial state.
+///
+/// This is the 64-bit FNV-1a hash of the XSB board, which does not change
+/// across runs or Rust versions.
+pub fn fingerprint(level: &Level) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in level.to_string().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Returns the path of the save file, in the XDG data directory.
+fn save_file() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data_home.join("sokoban-rs").join("progress.txt"))
+}
@@ -0,0 +1,462 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A solver that searches the push graph of a level with a uniform-cost search.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use game::{Direction, Level, Position, DIRECTIONS};
+
+/// Represents what the solver should minimize.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Minimize the number of moves, then the number of pushes
+    Moves,
+    /// Minimize the number of pushes, then the number of moves
+    Pushes,
+}
+
+/// Represents the limits of a search.
+#[derive(Copy, Clone, Debug)]
+pub struct Budget {
+    /// The maximum number of nodes to expand
+    pub nodes: Option<usize>,
+    /// The maximum duration of the search
+    pub time: Option<Duration>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            nodes: Some(1_000_000),
+            time: None,
+        }
+    }
+}
+
+/// Represents statistics about a search.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Statistics {
+    /// The number of nodes expanded
+    pub nodes: usize,
+    /// The deepest number of pushes reached
+    pub depth: usize,
+    /// The time spent searching
+    pub elapsed: Duration,
+}
+
+/// Represents a solution found by the solver.
+#[derive(Clone, Debug)]
+pub struct Solution {
+    /// The solution in LURD notation
+    pub lurd: String,
+    /// The number of moves
+    pub moves: usize,
+    /// The number of pushes
+    pub pushes: usize,
+}
+
+/// Represents the result of a search.
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    /// A solution was found
+    Solved(Solution),
+    /// The whole search space was explored without finding a solution
+    Unsolvable,
+    /// The budget was exhausted before the search could complete
+    Exhausted,
+}
+
+/// Searches for a solution of a level.
+pub struct Solver {
+    /// What the solver should minimize
+    mode: Mode,
+    /// The limits of the search
+    budget: Budget,
+}
+
+impl Solver {
+    /// Creates a new instance.
+    pub fn new(mode: Mode, budget: Budget) -> Solver {
+        Solver { mode, budget }
+    }
+
+    /// Searches for a solution starting from the current state of the level.
+    pub fn solve(&self, level: &Level) -> (Outcome, Statistics) {
+        let start = Instant::now();
+        let board = Board::new(level);
+        let mut stats = Statistics::default();
+        let outcome = self.search(&board, &mut stats, start);
+        stats.elapsed = start.elapsed();
+        (outcome, stats)
+    }
+
+    fn search(&self, board: &Board, stats: &mut Statistics, start: Instant) -> Outcome {
+        let root = State {
+            player: board.player,
+            boxes: board.boxes.clone(),
+        };
+        let mut nodes = vec![Node {
+            state: root.clone(),
+            parent: None,
+            push: None,
+            moves: 0,
+            pushes: 0,
+        }];
+        let mut best = HashMap::new();
+        best.insert(root, 0);
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((self.cost(&nodes[0]), 0)));
+
+        while let Some(Reverse((_, index))) = queue.pop() {
+            if best[&nodes[index].state] != index {
+                continue;
+            }
+            if board.is_solved(&nodes[index].state) {
+                return Outcome::Solved(board.solution(&nodes, index));
+            }
+            if self.is_exhausted(stats, start) {
+                return Outcome::Exhausted;
+            }
+            stats.nodes += 1;
+            stats.depth = stats.depth.max(nodes[index].pushes);
+
+            let (state, moves, pushes) = {
+                let node = &nodes[index];
+                (node.state.clone(), node.moves, node.pushes)
+            };
+            let distances = board.distances(&state);
+            for (b, &cell) in state.boxes.iter().enumerate() {
+                for &dir in &DIRECTIONS {
+                    let (behind, ahead) = match (
+                        board.neighbor(cell, dir.opposite()),
+                        board.neighbor(cell, dir),
+                    ) {
+                        (Some(behind), Some(ahead)) => (behind, ahead),
+                        _ => continue,
+                    };
+                    let walk = match distances[behind] {
+                        Some(walk) => walk,
+                        None => continue,
+                    };
+                    if board.dead[ahead] || state.boxes.binary_search(&ahead).is_ok() {
+                        continue;
+                    }
+
+                    let mut boxes = state.boxes.clone();
+                    boxes[b] = ahead;
+                    boxes.sort();
+                    let child = Node {
+                        state: State {
+                            player: cell,
+                            boxes,
+                        },
+                        parent: Some(index),
+                        push: Some(dir),
+                        moves: moves + walk + 1,
+                        pushes: pushes + 1,
+                    };
+                    let child_cost = self.cost(&child);
+                    let child_index = nodes.len();
+                    match best.entry(child.state.clone()) {
+                        Entry::Occupied(mut e) => {
+                            if self.cost(&nodes[*e.get()]) <= child_cost {
+                                continue;
+                            }
+                            e.insert(child_index);
+                        }
+                        Entry::Vacant(e) => {
+                            e.insert(child_index);
+                        }
+                    }
+                    nodes.push(child);
+                    queue.push(Reverse((child_cost, child_index)));
+                }
+            }
+        }
+
+        Outcome::Unsolvable
+    }
+
+    /// Returns the cost of a node according to the mode.
+    fn cost(&self, node: &Node) -> (usize, usize) {
+        match self.mode {
+            Mode::Moves => (node.moves, node.pushes),
+            Mode::Pushes => (node.pushes, node.moves),
+        }
+    }
+
+    /// Returns true if the budget has been used up.
+    fn is_exhausted(&self, stats: &Statistics, start: Instant) -> bool {
+        if let Some(max) = self.budget.nodes {
+            if stats.nodes >= max {
+                return true;
+            }
+        }
+        if let Some(max) = self.budget.time {
+            if start.elapsed() >= max {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Represents a position of the boxes and the player.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct State {
+    /// The cell of the player
+    player: usize,
+    /// The sorted cells of the boxes
+    boxes: Vec<usize>,
+}
+
+/// Represents a state reached during the search.
+struct Node {
+    /// The state reached
+    state: State,
+    /// The index of the node this one was reached from
+    parent: Option<usize>,
+    /// The direction of the push leading to this node
+    push: Option<Direction>,
+    /// The number of moves since the start
+    moves: usize,
+    /// The number of pushes since the start
+    pushes: usize,
+}
+
+/// A compact representation of the static parts of a level.
+struct Board {
+    /// The number of columns
+    width: i32,
+    /// The number of rows
+    height: i32,
+    /// Whether each cell is a wall
+    walls: Vec<bool>,
+    /// Whether each cell is a dead square
+    dead: Vec<bool>,
+    /// The sorted cells of the targets
+    squares: Vec<usize>,
+    /// The cell of the player
+    player: usize,
+    /// The sorted cells of the boxes
+    boxes: Vec<usize>,
+}
+
+impl Board {
+    fn new(level: &Level) -> Board {
+        let (width, height) = level.extents();
+        let mut board = Board {
+            width,
+            height,
+            walls: Vec::with_capacity((width * height) as usize),
+            dead: Vec::with_capacity((width * height) as usize),
+            squares: Vec::new(),
+            player: 0,
+            boxes: Vec::new(),
+        };
+        for r in 0..height {
+            for c in 0..width {
+                let pos = Position::new(r, c);
+                let cell = board.walls.len();
+                board.walls.push(level.is_wall(&pos));
+                board.dead.push(level.is_dead_square(&pos));
+                if level.is_square(&pos) {
+                    board.squares.push(cell);
+                }
+                if level.is_box(&pos) {
+                    board.boxes.push(cell);
+                }
+                if level.is_player(&pos) {
+                    board.player = cell;
+                }
+            }
+        }
+        board
+    }
+
+    /// Returns the cell next to the given one, unless it is a wall or off the board.
+    fn neighbor(&self, cell: usize, dir: Direction) -> Option<usize> {
+        let pos = Position::new(cell as i32 / self.width, cell as i32 % self.width).neighbor(dir);
+        if pos.row() < 0
+            || pos.row() >= self.height
+            || pos.column() < 0
+            || pos.column() >= self.width
+        {
+            return None;
+        }
+        let next = (pos.row() * self.width + pos.column()) as usize;
+        if self.walls[next] {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    /// Returns whether every target is covered by a box, as `Level::is_completed` does.
+    fn is_solved(&self, state: &State) -> bool {
+        self.squares
+            .iter()
+            .all(|s| state.boxes.binary_search(s).is_ok())
+    }
+
+    /// Returns the walking distance from the player to every cell.
+    fn distances(&self, state: &State) -> Vec<Option<usize>> {
+        self.walk(state, state.player, |_| false).0
+    }
+
+    /// Walks from a cell until the given predicate matches or every reachable cell is visited.
+    ///
+    /// Returns the distances along with, for each cell, the direction it was reached with.
+    fn walk<F: Fn(usize) -> bool>(
+        &self,
+        state: &State,
+        from: usize,
+        stop: F,
+    ) -> (Vec<Option<usize>>, Vec<Option<Direction>>) {
+        let mut distances = vec![None; self.walls.len()];
+        let mut directions = vec![None; self.walls.len()];
+        let mut queue = VecDeque::new();
+        distances[from] = Some(0);
+        queue.push_back(from);
+        while let Some(cell) = queue.pop_front() {
+            if stop(cell) {
+                break;
+            }
+            for &dir in &DIRECTIONS {
+                if let Some(next) = self.neighbor(cell, dir) {
+                    if distances[next].is_none() && state.boxes.binary_search(&next).is_err() {
+                        distances[next] = Some(distances[cell].unwrap() + 1);
+                        directions[next] = Some(dir);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        (distances, directions)
+    }
+
+    /// Rebuilds the LURD solution leading to the given node.
+    fn solution(&self, nodes: &[Node], index: usize) -> Solution {
+        let mut chain = vec![index];
+        while let Some(parent) = nodes[*chain.last().unwrap()].parent {
+            chain.push(parent);
+        }
+        chain.reverse();
+
+        let mut lurd = String::new();
+        for pair in chain.windows(2) {
+            let (from, to) = (&nodes[pair[0]], &nodes[pair[1]]);
+            let dir = to.push.unwrap();
+            let behind = self.neighbor(to.state.player, dir.opposite()).unwrap();
+            lurd.push_str(&self.path(&from.state, behind));
+            lurd.push(dir.to_char().to_ascii_uppercase());
+        }
+
+        let last = &nodes[index];
+        Solution {
+            lurd,
+            moves: last.moves,
+            pushes: last.pushes,
+        }
+    }
+
+    /// Returns the shortest walk of the player to the given cell in LURD notation.
+    fn path(&self, state: &State, to: usize) -> String {
+        let (_, directions) = self.walk(state, state.player, |cell| cell == to);
+        let mut path = Vec::new();
+        let mut cell = to;
+        while cell != state.player {
+            let dir = directions[cell].unwrap();
+            path.push(dir.to_char());
+            cell = self.neighbor(cell, dir.opposite()).unwrap();
+        }
+        path.iter().rev().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A level whose solutions with the fewest moves and with the fewest pushes differ
+    const TWO_BOXES: &str = "######\n#   .#\n#  $$#\n#  @.#\n######";
+
+    fn solve(board: &str, mode: Mode, budget: Budget) -> Outcome {
+        let level: Level = board.parse().unwrap();
+        Solver::new(mode, budget).solve(&level).0
+    }
+
+    fn check_solution(board: &str, outcome: Outcome, moves: usize, pushes: usize) {
+        let solution = match outcome {
+            Outcome::Solved(solution) => solution,
+            outcome => panic!("not solved: {:?}", outcome),
+        };
+        assert_eq!((solution.moves, solution.pushes), (moves, pushes));
+        let mut level: Level = board.parse().unwrap();
+        level.replay(&solution.lurd).unwrap();
+        assert!(level.is_completed());
+        assert_eq!(level.to_lurd(), solution.lurd);
+    }
+
+    #[test]
+    fn solves_with_fewest_moves() {
+        let outcome = solve(TWO_BOXES, Mode::Moves, Budget::default());
+        check_solution(TWO_BOXES, outcome, 10, 5);
+    }
+
+    #[test]
+    fn solves_with_fewest_pushes() {
+        let outcome = solve(TWO_BOXES, Mode::Pushes, Budget::default());
+        check_solution(TWO_BOXES, outcome, 12, 3);
+    }
+
+    #[test]
+    fn reports_unsolvable_level() {
+        let board = "#####\n#$ .#\n#@  #\n#####";
+        match solve(board, Mode::Pushes, Budget::default()) {
+            Outcome::Unsolvable => {}
+            outcome => panic!("expected unsolvable: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn covers_every_target() {
+        let board = "######\n#@$..#\n######";
+        match solve(board, Mode::Pushes, Budget::default()) {
+            Outcome::Unsolvable => {}
+            outcome => panic!("expected unsolvable: {:?}", outcome),
+        }
+
+        let board = "######\n#@$ .#\n# $  #\n######";
+        let outcome = solve(board, Mode::Moves, Budget::default());
+        check_solution(board, outcome, 2, 2);
+    }
+
+    #[test]
+    fn gives_up_when_out_of_budget() {
+        let budget = Budget {
+            nodes: Some(1),
+            time: None,
+        };
+        match solve(TWO_BOXES, Mode::Moves, budget) {
+            Outcome::Exhausted => {}
+            outcome => panic!("expected to give up: {:?}", outcome),
+        }
+    }
+}
@@ -66,6 +66,11 @@ impl<'a> Tileset<'a> {
         &self.texture
     }
 
+    /// Returns the associated texture for modification
+    pub fn texture_mut(&mut self) -> &mut Texture<'a> {
+        &mut self.texture
+    }
+
     /// Returns the width of a tile.
     pub fn width(&self) -> u32 {
         self.width
@@ -114,6 +119,16 @@ impl<'a> Tileset<'a> {
         (x, y)
     }
 
+    /// Returns the position whose floor contains the given coordinates.
+    ///
+    /// This is the inverse of `get_coordinates`, taking into account that the
+    /// visible top of a floor tile starts at the offset of the items.
+    pub fn get_position(&self, x: i32, y: i32) -> Position {
+        let col = x.div_euclid(self.width as i32);
+        let row = (y - self.offset).div_euclid(self.effective_height as i32);
+        Position::new(row, col)
+    }
+
     /// Returns the full size needed to draw a level of the given dimensions.
     pub fn get_rendering_size(&self, extents: (i32, i32)) -> (u32, u32) {
         let width = extents.0 as u32 * self.width;
@@ -142,6 +157,8 @@ pub struct TilesetSelector<'a> {
     big_set: Tileset<'a>,
     /// The small tileset
     small_set: Tileset<'a>,
+    /// Whether the small tileset is selected regardless of the extents
+    force_small: bool,
 }
 
 impl<'a> TilesetSelector<'a> {
@@ -153,6 +170,7 @@ impl<'a> TilesetSelector<'a> {
             extents: (0, 0),
             big_set,
             small_set,
+            force_small: false,
         }
     }
 
@@ -161,11 +179,29 @@ impl<'a> TilesetSelector<'a> {
         self.extents = extents;
     }
 
+    /// Selects the small tileset regardless of the extents, or stops doing so.
+    pub fn force_small(&mut self, force: bool) {
+        self.force_small = force;
+    }
+
     pub fn select(&self) -> &Tileset {
-        if cmp::max(self.extents.0, self.extents.1) > TilesetSelector::THRESHOLD {
+        if self.is_small() {
             &self.small_set
         } else {
             &self.big_set
         }
     }
+
+    pub fn select_mut(&mut self) -> &mut Tileset<'a> {
+        if self.is_small() {
+            &mut self.small_set
+        } else {
+            &mut self.big_set
+        }
+    }
+
+    /// Returns true if the small tileset is selected.
+    fn is_small(&self) -> bool {
+        self.force_small || cmp::max(self.extents.0, self.extents.1) > TilesetSelector::THRESHOLD
+    }
 }
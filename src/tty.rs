@@ -0,0 +1,605 @@
+// This file is part of sokoban-rs
+// Copyright 2015 Sébastien Watteau
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The terminal frontend, for playing without a display.
+//!
+//! The board is drawn with ANSI escape sequences, two columns per square so
+//! that it keeps its proportions. Unicode glyphs are used when the locale
+//! supports UTF-8, plain XSB characters otherwise.
+
+use std::collections::VecDeque;
+use std::env;
+use std::io::{self, Read, Write};
+
+use game::{Direction, Level, Position};
+use {Game, Selection};
+
+/// Represents a key typed on the terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// The up arrow
+    Up,
+    /// The down arrow
+    Down,
+    /// The left arrow
+    Left,
+    /// The right arrow
+    Right,
+    /// The Page Up key
+    PageUp,
+    /// The Page Down key
+    PageDown,
+    /// The Home key
+    Home,
+    /// The End key
+    End,
+    /// The Enter key
+    Enter,
+    /// The Escape key
+    Escape,
+    /// A printable character, in lower case
+    Char(char),
+    /// A letter typed with the Ctrl key, in lower case
+    Ctrl(char),
+    /// Any other key
+    Other,
+}
+
+/// The glyphs used to draw the board
+struct Glyphs {
+    /// A wall
+    wall: &'static str,
+    /// An empty floor tile
+    floor: &'static str,
+    /// An empty target
+    square: &'static str,
+    /// A highlighted dead square
+    dead_square: &'static str,
+    /// A box
+    box_: &'static str,
+    /// A box on a target
+    box_on_square: &'static str,
+    /// The player
+    player: &'static str,
+    /// The player on a target
+    player_on_square: &'static str,
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    wall: "██",
+    floor: "  ",
+    square: "• ",
+    dead_square: "× ",
+    box_: "[]",
+    box_on_square: "[]",
+    player: "@ ",
+    player_on_square: "@ ",
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    wall: "##",
+    floor: "  ",
+    square: ". ",
+    dead_square: "x ",
+    box_: "$ ",
+    box_on_square: "* ",
+    player: "@ ",
+    player_on_square: "+ ",
+};
+
+// The ANSI colors of the board and of the warnings
+const WALL_COLOR: &str = "\x1b[34m";
+const SQUARE_COLOR: &str = "\x1b[31m";
+const DEAD_SQUARE_COLOR: &str = "\x1b[2;31m";
+const BOX_COLOR: &str = "\x1b[33m";
+const BOX_ON_SQUARE_COLOR: &str = "\x1b[1;32m";
+const DEADLOCKED_BOX_COLOR: &str = "\x1b[1;31m";
+const PLAYER_COLOR: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+/// How long to wait for the rest of an escape sequence, in milliseconds
+const ESCAPE_DELAY_MS: i32 = 50;
+
+/// Draws onto the terminal and reads the keys typed.
+///
+/// The terminal is switched to raw mode and to the alternate screen while an
+/// instance is alive, and restored when it is dropped.
+pub struct Terminal {
+    /// The terminal settings to restore
+    raw_mode: RawMode,
+    /// The glyphs used to draw the board
+    glyphs: &'static Glyphs,
+    /// Bytes read from the terminal but not yet turned into keys
+    input: VecDeque<u8>,
+    /// Whether the dead squares are highlighted
+    show_dead_squares: bool,
+    /// A message to display in the status line
+    notice: Option<String>,
+    /// The best moves and pushes recorded for the current level
+    best: Option<(usize, usize)>,
+}
+
+impl Terminal {
+    /// Takes control of the terminal.
+    pub fn open() -> io::Result<Terminal> {
+        let raw_mode = RawMode::enable()?;
+        let mut terminal = Terminal {
+            raw_mode,
+            glyphs: if is_utf8_locale() {
+                &UNICODE_GLYPHS
+            } else {
+                &ASCII_GLYPHS
+            },
+            input: VecDeque::new(),
+            show_dead_squares: false,
+            notice: None,
+            best: None,
+        };
+        // Switch to the alternate screen and hide the cursor
+        terminal.write("\x1b[?1049h\x1b[?25l")?;
+        Ok(terminal)
+    }
+
+    /// Sets the message displayed in the status line until the next key.
+    pub fn set_notice<S: Into<String>>(&mut self, notice: S) {
+        self.notice = Some(notice.into());
+    }
+
+    /// Removes the message displayed in the status line.
+    pub fn clear_notice(&mut self) {
+        self.notice = None;
+    }
+
+    /// Sets the best moves and pushes shown in the status line.
+    pub fn set_best(&mut self, best: Option<(usize, usize)>) {
+        self.best = best;
+    }
+
+    /// Toggles the highlighting of dead squares.
+    pub fn toggle_dead_squares(&mut self) {
+        self.show_dead_squares = !self.show_dead_squares;
+    }
+
+    /// Draws a level, followed by the status lines.
+    pub fn paint(&mut self, level: &Level) -> io::Result<()> {
+        let glyphs = self.glyphs;
+        let (cols, rows) = level.extents();
+        let mut lines = Vec::new();
+        for r in 0..rows {
+            let mut line = String::new();
+            for c in 0..cols {
+                let pos = Position::new(r, c);
+                let square = level.is_square(&pos);
+                let (color, glyph) = if level.is_wall(&pos) {
+                    (WALL_COLOR, glyphs.wall)
+                } else if level.is_player(&pos) {
+                    if square {
+                        (PLAYER_COLOR, glyphs.player_on_square)
+                    } else {
+                        (PLAYER_COLOR, glyphs.player)
+                    }
+                } else if level.is_box(&pos) {
+                    if square {
+                        (BOX_ON_SQUARE_COLOR, glyphs.box_on_square)
+                    } else if level.is_deadlocked_box(&pos) {
+                        (DEADLOCKED_BOX_COLOR, glyphs.box_)
+                    } else {
+                        (BOX_COLOR, glyphs.box_)
+                    }
+                } else if square {
+                    (SQUARE_COLOR, glyphs.square)
+                } else if self.show_dead_squares && level.is_dead_square(&pos) {
+                    (DEAD_SQUARE_COLOR, glyphs.dead_square)
+                } else {
+                    ("", glyphs.floor)
+                };
+                if color.is_empty() {
+                    line.push_str(glyph);
+                } else {
+                    line.push_str(&format!("{}{}{}", color, glyph, RESET));
+                }
+            }
+            lines.push(line);
+        }
+        lines.push(String::new());
+
+        // The number of moves and pushes, along with the best ones
+        let mut status = format!(
+            "moves / pushes: {} / {}",
+            level.get_steps(),
+            level.get_pushes()
+        );
+        if let Some((moves, pushes)) = self.best {
+            status.push_str(&format!("  (best: {} / {})", moves, pushes));
+        }
+
+        // The notice, or a warning about boxes that can no longer reach targets
+        if let Some(ref notice) = self.notice {
+            status.push_str(&format!("  {}", notice));
+        } else if level.has_dead_box() {
+            status.push_str(&format!("  {}dead box!{}", DEADLOCKED_BOX_COLOR, RESET));
+        } else if level.is_deadlocked() {
+            status.push_str(&format!("  {}deadlock!{}", DEADLOCKED_BOX_COLOR, RESET));
+        }
+        lines.push(status);
+
+        // The level's title and author
+        if level.copyright().is_empty() {
+            lines.push(level.title().to_string());
+        } else {
+            lines.push(format!("{} by {}", level.title(), level.copyright()));
+        }
+        lines.push("\x1b[2marrows: move  U: undo  R: retry  N/P: next/previous  L: levels  D: dead squares  Esc: quit\x1b[0m".to_string());
+        self.paint_lines(&lines)
+    }
+
+    /// Draws the list of the levels of a collection, with the selected one highlighted.
+    pub fn paint_level_select(&mut self, game: &Game, selected: usize) -> io::Result<()> {
+        let levels = &game.levels;
+        let visible = self.level_select_rows();
+        let first = selected
+            .saturating_sub(visible / 2)
+            .min(levels.len().saturating_sub(visible));
+
+        let mut lines = vec![format!("{}  -  choose a level", game.name), String::new()];
+        for (index, level) in levels.iter().enumerate().skip(first).take(visible) {
+            let mut line = format!("{:>4}  {}", level.number(), level.title());
+            if let Some((moves, pushes)) = game.best_score(level) {
+                line.push_str(&format!("  (best: {} / {})", moves, pushes));
+            }
+            if index == selected {
+                line = format!("\x1b[7m{}{}", line, RESET);
+            }
+            lines.push(line);
+        }
+        self.paint_lines(&lines)
+    }
+
+    /// Returns the number of levels listed at once by the level select screen.
+    pub fn level_select_rows(&self) -> usize {
+        (terminal_rows() as usize).saturating_sub(2).max(1)
+    }
+
+    /// Waits for the next key.
+    pub fn read_key(&mut self) -> io::Result<Key> {
+        loop {
+            if let Some(key) = self.parse_key(false) {
+                return Ok(key);
+            }
+            // An escape sequence may be split across reads, over a slow
+            // connection: only give up on it once no more bytes arrive.
+            if !self.input.is_empty() && !wait_for_input(ESCAPE_DELAY_MS)? {
+                if let Some(key) = self.parse_key(true) {
+                    return Ok(key);
+                }
+            }
+            let mut buf = [0; 64];
+            let n = io::stdin().read(&mut buf)?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.input.extend(&buf[..n]);
+        }
+    }
+
+    /// Turns the first bytes of the input into a key, if any.
+    ///
+    /// An escape sequence that is not complete yet is left in the input,
+    /// unless `flush` is set: a lone escape byte is then the Escape key itself.
+    fn parse_key(&mut self, flush: bool) -> Option<Key> {
+        let byte = *self.input.front()?;
+        let key = match byte {
+            0x1b => match self.input.get(1) {
+                Some(&b'[') | Some(&b'O') => {
+                    let end = self
+                        .input
+                        .iter()
+                        .skip(2)
+                        .position(|b| (0x40..=0x7e).contains(b))
+                        .map(|i| i + 2);
+                    match end {
+                        Some(end) => {
+                            let sequence: Vec<u8> = self.input.drain(..=end).collect();
+                            let params: String =
+                                sequence[2..end].iter().map(|&b| b as char).collect();
+                            return Some(parse_sequence(&params, sequence[end] as char));
+                        }
+                        None if flush => {
+                            self.input.clear();
+                            return Some(Key::Other);
+                        }
+                        None => return None,
+                    }
+                }
+                None if !flush => return None,
+                _ => Key::Escape,
+            },
+            b'\r' | b'\n' => Key::Enter,
+            0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+            0x20..=0x7e => Key::Char((byte as char).to_ascii_lowercase()),
+            _ => Key::Other,
+        };
+        self.input.pop_front();
+        Some(key)
+    }
+
+    /// Replaces the screen with the given lines.
+    fn paint_lines(&mut self, lines: &[String]) -> io::Result<()> {
+        let mut screen = String::from("\x1b[H");
+        for line in lines {
+            screen.push_str(line);
+            screen.push_str("\x1b[K\r\n");
+        }
+        screen.push_str("\x1b[J");
+        self.write(&screen)
+    }
+
+    /// Writes to the terminal at once.
+    fn write(&mut self, s: &str) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        out.write_all(s.as_bytes())?;
+        out.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // Show the cursor and leave the alternate screen
+        let _ = self.write("\x1b[?25h\x1b[?1049l");
+        self.raw_mode.disable();
+    }
+}
+
+/// Returns the key sent as an escape sequence, given its parameters and final character.
+fn parse_sequence(params: &str, last: char) -> Key {
+    match (params, last) {
+        (_, 'A') => Key::Up,
+        (_, 'B') => Key::Down,
+        (_, 'C') => Key::Right,
+        (_, 'D') => Key::Left,
+        (_, 'H') | ("1", '~') | ("7", '~') => Key::Home,
+        (_, 'F') | ("4", '~') | ("8", '~') => Key::End,
+        ("5", '~') => Key::PageUp,
+        ("6", '~') => Key::PageDown,
+        _ => Key::Other,
+    }
+}
+
+/// Returns true if the locale tells that the terminal understands UTF-8.
+fn is_utf8_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+}
+
+/// Keeps the terminal in raw mode, so that keys are read as soon as they are
+/// typed and are not echoed.
+#[cfg(unix)]
+struct RawMode {
+    /// The settings to restore
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    /// Switches the terminal to raw mode.
+    fn enable() -> io::Result<RawMode> {
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 {
+                return Err(io::Error::other("not a terminal"));
+            }
+            let mut original = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { original })
+        }
+    }
+
+    /// Restores the terminal settings.
+    fn disable(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
+
+/// Returns the number of rows of the terminal.
+#[cfg(unix)]
+fn terminal_rows() -> u16 {
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_row > 0 {
+            size.ws_row
+        } else {
+            24
+        }
+    }
+}
+
+/// Waits until input is available on the terminal, for at most the given
+/// number of milliseconds.
+///
+/// Returns false if the delay expired first.
+#[cfg(unix)]
+fn wait_for_input(timeout: i32) -> io::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut fd, 1, timeout) } {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n > 0),
+    }
+}
+
+#[cfg(not(unix))]
+struct RawMode;
+
+#[cfg(not(unix))]
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        Err(io::Error::other(
+            "the terminal frontend needs a Unix terminal",
+        ))
+    }
+
+    fn disable(&mut self) {}
+}
+
+#[cfg(not(unix))]
+fn terminal_rows() -> u16 {
+    24
+}
+
+#[cfg(not(unix))]
+fn wait_for_input(_timeout: i32) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Plays a level collection in the terminal.
+pub fn mainloop(game: &mut Game, start: usize, notice: Option<String>) -> io::Result<()> {
+    let levels = &game.levels;
+    if levels.is_empty() {
+        return Ok(());
+    }
+    let mut terminal = Terminal::open()?;
+    if let Some(notice) = notice {
+        terminal.set_notice(notice);
+    }
+    let mut index = start.min(levels.len() - 1);
+    let mut level = levels[index].clone();
+    terminal.set_best(game.best_score(&levels[index]));
+
+    loop {
+        // Show the completed level until a key is typed, then go to the next one
+        if level.is_completed() {
+            game.progress.update(game.name, &levels[index], &level);
+            let last = index + 1 == levels.len();
+            let mut notice = if last {
+                "collection completed, press a key to quit".to_string()
+            } else {
+                "level completed, press a key to continue".to_string()
+            };
+            if let Err(err) = game.progress.save() {
+                notice = format!("cannot save progress: {}; {}", err, notice);
+            }
+            terminal.set_best(game.best_score(&levels[index]));
+            terminal.set_notice(notice);
+            terminal.paint(&level)?;
+            let key = terminal.read_key()?;
+            terminal.clear_notice();
+            if last || key == Key::Escape || key == Key::Ctrl('c') {
+                return Ok(());
+            }
+            index += 1;
+            level = levels[index].clone();
+            terminal.set_best(game.best_score(&levels[index]));
+        }
+
+        terminal.paint(&level)?;
+        let key = terminal.read_key()?;
+        terminal.clear_notice();
+
+        match key {
+            Key::Escape | Key::Ctrl('c') => return Ok(()),
+            Key::Left => {
+                level.step(Direction::Left);
+            }
+            Key::Right => {
+                level.step(Direction::Right);
+            }
+            Key::Up => {
+                level.step(Direction::Up);
+            }
+            Key::Down => {
+                level.step(Direction::Down);
+            }
+            Key::Char('r') => {
+                level = levels[index].clone();
+            }
+            Key::Char('n') => {
+                if index + 1 == levels.len() {
+                    return Ok(());
+                }
+                index += 1;
+                level = levels[index].clone();
+                terminal.set_best(game.best_score(&levels[index]));
+            }
+            Key::Char('p') if index > 0 => {
+                index -= 1;
+                level = levels[index].clone();
+                terminal.set_best(game.best_score(&levels[index]));
+            }
+            Key::Char('d') => {
+                terminal.toggle_dead_squares();
+            }
+            Key::Char('l') => match select_level(&mut terminal, game, index)? {
+                Selection::Level(i) => {
+                    index = i;
+                    level = levels[index].clone();
+                    terminal.set_best(game.best_score(&levels[index]));
+                }
+                Selection::Cancel => {}
+                Selection::Quit => return Ok(()),
+            },
+            Key::Char('e') if game.file.is_some() => {
+                terminal.set_notice("the level editor needs a display");
+            }
+            Key::Char('u') | Key::Ctrl('z') => {
+                level.undo();
+            }
+            Key::Ctrl('y') => {
+                level.redo();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lets the player choose a level of the collection in a list.
+fn select_level(terminal: &mut Terminal, game: &Game, current: usize) -> io::Result<Selection> {
+    let last = game.levels.len() - 1;
+    let mut selected = current;
+    loop {
+        terminal.paint_level_select(game, selected)?;
+        let page = terminal.level_select_rows();
+
+        match terminal.read_key()? {
+            Key::Ctrl('c') => return Ok(Selection::Quit),
+            Key::Escape | Key::Char('l') => return Ok(Selection::Cancel),
+            Key::Enter => return Ok(Selection::Level(selected)),
+            Key::Up | Key::Left => selected = selected.saturating_sub(1),
+            Key::Down | Key::Right => selected = (selected + 1).min(last),
+            Key::PageUp => selected = selected.saturating_sub(page),
+            Key::PageDown => selected = (selected + page).min(last),
+            Key::Home => selected = 0,
+            Key::End => selected = last,
+            _ => {}
+        }
+    }
+}